@@ -0,0 +1,241 @@
+//! Per-frame cost of UI text with and without the text cache, over a menu
+//! that doesn't change and the same menu with a timer ticking every frame.
+//!
+//! Uploads and bytes per frame are printed before each group is timed.
+//! Without the cache means a fresh [`TextCache`] every frame, so every string
+//! is laid out and uploaded again like it was before the cache.
+
+// `cargo clippy --all-targets` checks benchmarks with `cfg(test)` but without
+// the test harness, which leaves the game's test modules with unused imports
+#![cfg_attr(test, allow(unused_imports))]
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use render::{
+    assets::AssetManager,
+    bindings::{CameraBinder, CameraBinding},
+    buffer::BackedBuffer,
+    font::{Font, FontId, FontRegistry, TextPipeline, TextStyle},
+    resources::FsResources,
+    rich_text::{SpanStyle, TextSpan},
+    text_cache::{TextCache, TextCacheStats},
+    text_layout::TextLayout,
+    uniform::CameraData,
+};
+
+// The game is a binary, so the benchmark compiles its modules in directly.
+// Most of what's in them goes unused here.
+#[allow(dead_code)]
+#[path = "../src/app.rs"]
+mod app;
+#[allow(dead_code)]
+#[path = "../src/game/mod.rs"]
+mod game;
+#[allow(dead_code)]
+#[path = "../src/input.rs"]
+mod input;
+#[allow(dead_code)]
+#[path = "../src/render/mod.rs"]
+mod render;
+#[allow(dead_code)]
+#[path = "../src/settings.rs"]
+mod settings;
+#[allow(dead_code)]
+#[path = "../src/state/mod.rs"]
+mod state;
+#[allow(dead_code)]
+#[path = "../src/ui/mod.rs"]
+mod ui;
+
+/// Frames averaged over for the printed stats
+const FRAMES: u32 = 120;
+
+const ROWS: [&str; 8] = [
+    "Fullscreen: Off",
+    "VSync: On",
+    "MSAA: 4x",
+    "UI scale: 100%",
+    "Bloom: On",
+    "Screen shake: On",
+    "Anyone can resume: Off",
+    "Back",
+];
+
+struct Bench {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: TextPipeline,
+    fonts: FontRegistry,
+    font: FontId,
+    camera_binding: CameraBinding,
+    target: wgpu::TextureView,
+}
+
+impl Bench {
+    fn new() -> Self {
+        let (device, queue) = pollster::block_on(async {
+            let instance = wgpu::Instance::new(&Default::default());
+            let adapter = match instance.request_adapter(&Default::default()).await {
+                Ok(adapter) => adapter,
+                // Software fallback, the numbers are still comparable
+                Err(_) => instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        force_fallback_adapter: true,
+                        ..Default::default()
+                    })
+                    .await
+                    .expect("No adapter to benchmark with"),
+            };
+            adapter.request_device(&Default::default()).await.unwrap()
+        });
+        let assets = AssetManager::new(
+            FsResources::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res")),
+            &device,
+            &queue,
+        );
+        let camera_binder = CameraBinder::new(&device);
+        let pipeline =
+            TextPipeline::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb, &camera_binder)
+                .unwrap();
+        let mut fonts = FontRegistry::new();
+        let font = fonts
+            .register(
+                &device,
+                &pipeline,
+                "ui",
+                assets
+                    .load_blocking::<Font>("fonts/OpenSans MSDF.zip")
+                    .unwrap(),
+            )
+            .unwrap();
+        let camera = BackedBuffer::with_data(
+            &device,
+            vec![CameraData::IDENTITY],
+            wgpu::BufferUsages::UNIFORM,
+        );
+        let camera_binding = camera_binder.bind(&device, &camera);
+        let target = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 640,
+                    height: 360,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+        Self {
+            device,
+            queue,
+            pipeline,
+            fonts,
+            font,
+            camera_binding,
+            target,
+        }
+    }
+
+    /// Queues, prepares and draws a frame of `texts`, returning the cache's
+    /// stats for it.
+    fn frame(&self, cache: &mut TextCache, texts: &[(String, TextLayout)]) -> TextCacheStats {
+        for (text, layout) in texts {
+            cache.queue(
+                &self.device,
+                &self.pipeline,
+                &self.fonts,
+                self.font,
+                &[TextSpan::new(text.as_str(), SpanStyle::default())],
+                layout,
+                TextStyle::default(),
+            );
+        }
+        cache.prepare(&self.device, &self.queue);
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.target,
+                    resolve_target: None,
+                    ops: Default::default(),
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            cache.draw(&mut pass, &self.pipeline, &self.fonts, &self.camera_binding);
+        }
+        self.queue.submit([encoder.finish()]);
+        cache.take_stats()
+    }
+
+    /// Draws `ui` for a frame, with a fresh cache every frame unless `cache`
+    /// is given.
+    fn run(&self, cache: Option<&mut TextCache>, ui: &[(String, TextLayout)]) -> TextCacheStats {
+        match cache {
+            Some(cache) => self.frame(cache, ui),
+            None => self.frame(&mut TextCache::new(), ui),
+        }
+    }
+}
+
+/// A settings menu's worth of text, with a timer that counts up every frame
+/// when `ticking`.
+fn menu(frame: u32, ticking: bool) -> Vec<(String, TextLayout)> {
+    let at = |x, y| TextLayout::at(glam::vec2(x, y));
+    let mut texts = vec![("Settings".to_string(), at(40.0, 20.0))];
+    for (i, row) in ROWS.iter().enumerate() {
+        texts.push((row.to_string(), at(40.0, 80.0 + 30.0 * i as f32)));
+    }
+    let ticks = if ticking { frame } else { 0 };
+    texts.push((
+        format!("{:02}:{:02}", ticks / 60 % 60, ticks % 60),
+        at(520.0, 20.0),
+    ));
+    texts
+}
+
+fn text_cache(c: &mut Criterion) {
+    let bench = Bench::new();
+
+    for (scene, ticking) in [("static", false), ("changing", true)] {
+        for cached in [true, false] {
+            let name = format!("{scene}/{}", if cached { "cached" } else { "uncached" });
+
+            // Stats are for frames after the first, which always uploads
+            let mut cache = TextCache::new();
+            bench.frame(&mut cache, &menu(0, ticking));
+            let mut total = TextCacheStats::default();
+            for frame in 1..=FRAMES {
+                let stats = bench.run(cached.then_some(&mut cache), &menu(frame, ticking));
+                total.layouts += stats.layouts;
+                total.uploads += stats.uploads;
+                total.uploaded_bytes += stats.uploaded_bytes;
+            }
+            println!(
+                "{name}: {:.2} layouts, {:.2} uploads and {} bytes per frame",
+                total.layouts as f32 / FRAMES as f32,
+                total.uploads as f32 / FRAMES as f32,
+                total.uploaded_bytes / FRAMES as u64,
+            );
+
+            let mut frame = FRAMES;
+            c.bench_function(&name, |b| {
+                b.iter(|| {
+                    frame += 1;
+                    bench.run(cached.then_some(&mut cache), &menu(frame, ticking))
+                })
+            });
+        }
+    }
+}
+
+criterion_group!(benches, text_cache);
+criterion_main!(benches);
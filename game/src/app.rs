@@ -6,30 +6,31 @@ use std::{
 
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, DeviceId as WinitDeviceId, RawKeyEvent, WindowEvent},
-    event_loop::{self, ActiveEventLoop, EventLoop},
-    keyboard::{KeyCode, PhysicalKey},
-    window::WindowAttributes,
+    dpi::PhysicalSize,
+    event::{
+        DeviceEvent, DeviceId as WinitDeviceId, KeyEvent, RawKeyEvent, StartCause, WindowEvent,
+    },
+    event_loop::{self, ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window, WindowAttributes},
 };
 
 use crate::{
-    game::{Game, Input, InputEvent, PlayerId},
-    render::{resources::FsResources, Renderer},
+    game::animation::AnimationLibrary,
+    input::{DeviceId, DeviceInput, InputAction},
+    render::{
+        Renderer,
+        resources::{GameResources, Resources},
+        utils::Rect,
+    },
+    settings::{Settings, WindowMode},
+    state::{Context, MainMenu, StateStack},
+    ui::NavAction,
 };
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
-pub struct Settings {
-    fullscreen: bool,
-}
-
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum DeviceId {
-    Winit(WinitDeviceId),
-    Gamepad(gilrs::GamepadId),
-}
-
 pub enum AppEvent {
-    RendererCreated(Renderer),
+    RendererCreated(Box<Renderer>),
     RendererFailed,
 }
 
@@ -42,33 +43,76 @@ impl std::fmt::Debug for AppEvent {
     }
 }
 
+/// Where screenshots are saved, relative to the working directory
+const SCREENSHOT_DIR: &str = "screenshots";
+
 pub struct App {
+    window: Option<Arc<Window>>,
     renderer: Option<Renderer>,
-    game: Game,
+    states: StateStack,
+    context: Context,
+    /// Settings the window and renderer were last set up with
+    applied: Settings,
+    /// What Alt+Enter switches to from windowed
+    last_fullscreen: WindowMode,
+    modifiers: ModifiersState,
+    /// When to draw the next frame with a frame rate cap
+    next_frame: Instant,
     proxy: event_loop::EventLoopProxy<AppEvent>,
     gamepads: gilrs::Gilrs,
     accumulator: Duration,
     game_timer: Instant,
-    players: HashMap<DeviceId, PlayerId>,
     wasd: [f32; 4],
-    resources: FsResources,
+    resources: GameResources,
+    animations: Arc<AnimationLibrary>,
+    /// Menu navigation since the last frame
+    ui_actions: Vec<(DeviceId, NavAction)>,
+    /// Which way each gamepad's stick was last pushed, so holding it only
+    /// moves once
+    nav_sticks: HashMap<gilrs::GamepadId, glam::IVec2>,
 }
 
 impl App {
     pub fn new(event_loop: &EventLoop<AppEvent>) -> Self {
         let proxy = event_loop.create_proxy();
         let gamepads = gilrs::GilrsBuilder::new().build().unwrap();
-        let resources = FsResources::new("game/res");
+        let resources = GameResources::new("game/res");
+        let animations = Arc::new(
+            pollster::block_on(resources.load_string_async("animations.json"))
+                .and_then(|json| AnimationLibrary::from_json(&json))
+                .expect("Unable to load animations"),
+        );
+        let settings = Settings::load().unwrap_or_else(|e| {
+            log::error!("Unable to load settings, using the defaults: {e:#}");
+            Settings::default()
+        });
         Self {
             gamepads,
+            window: None,
             renderer: None,
             proxy,
-            game: Game::new(Duration::from_secs_f32(1.0)),
+            states: StateStack::new(Box::new(MainMenu)),
+            context: Context {
+                animations: animations.clone(),
+                screen_size: glam::UVec2::ONE,
+                scale_factor: 1.0,
+                debug_draw: false,
+                settings: settings.clone(),
+            },
+            last_fullscreen: match settings.window_mode {
+                WindowMode::Windowed => WindowMode::Borderless,
+                mode => mode,
+            },
+            applied: settings,
+            modifiers: ModifiersState::empty(),
+            next_frame: Instant::now(),
             accumulator: Duration::ZERO,
             game_timer: Instant::now(),
-            players: HashMap::new(),
             wasd: [0.0; 4],
             resources,
+            animations,
+            ui_actions: Vec::new(),
+            nav_sticks: HashMap::new(),
         }
     }
 
@@ -83,16 +127,71 @@ impl App {
             proxy.send_event(event).unwrap();
         });
     }
+
+    /// Turns a stick crossing the halfway point into a single nav action.
+    fn stick_nav(&mut self, id: gilrs::GamepadId, axis: gilrs::Axis, amount: f32) {
+        let direction = if amount > 0.5 {
+            1
+        } else if amount < -0.5 {
+            -1
+        } else {
+            0
+        };
+        let device = DeviceId::Gamepad(id);
+        let stick = self.nav_sticks.entry(id).or_default();
+        let (previous, [negative, positive]) = match axis {
+            gilrs::Axis::LeftStickX => (&mut stick.x, [NavAction::Left, NavAction::Right]),
+            // Stick y points up
+            gilrs::Axis::LeftStickY => (&mut stick.y, [NavAction::Down, NavAction::Up]),
+            _ => return,
+        };
+        if direction != *previous {
+            match direction {
+                -1 => self.ui_actions.push((device, negative)),
+                1 => self.ui_actions.push((device, positive)),
+                _ => {}
+            }
+        }
+        *previous = direction;
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let settings = &mut self.context.settings;
+        settings.window_mode = match settings.window_mode {
+            WindowMode::Windowed => self.last_fullscreen,
+            _ => WindowMode::Windowed,
+        };
+        if let Err(e) = settings.save() {
+            log::error!("Unable to save settings: {e:#}");
+        }
+    }
+
+    fn input(&mut self, device: DeviceId, action: InputAction) {
+        self.states
+            .input(&mut self.context, &DeviceInput::new(device, action));
+    }
 }
 
 impl ApplicationHandler<AppEvent> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window_attributes = WindowAttributes::default();
+        let settings = &self.context.settings;
+        let [width, height] = settings.resolution;
+        let monitor = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next());
+        let window_attributes = WindowAttributes::default()
+            .with_inner_size(PhysicalSize::new(width, height))
+            .with_fullscreen(fullscreen(settings, monitor));
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        self.context.scale_factor = window.scale_factor() as f32;
+        self.window = Some(window.clone());
         let resources = self.resources.clone();
-        self.spawn_task(move || async {
-            match Renderer::new(window, resources).await {
-                Ok(renderer) => AppEvent::RendererCreated(renderer),
+        let animations = self.animations.clone();
+        let options = settings.surface_options();
+        self.applied = settings.clone();
+        self.spawn_task(move || async move {
+            match Renderer::new(window, resources, animations, options).await {
+                Ok(renderer) => AppEvent::RendererCreated(Box::new(renderer)),
                 Err(e) => {
                     log::error!("Failed to create renderer {}", e);
                     AppEvent::RendererFailed
@@ -102,37 +201,50 @@ impl ApplicationHandler<AppEvent> for App {
         self.game_timer = Instant::now();
     }
 
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let StartCause::ResumeTimeReached { .. } = cause
+            && let Some(window) = &self.window
+        {
+            window.request_redraw();
+        }
+    }
+
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        // let mut
         while let Some(event) = self.gamepads.next_event() {
-            let player = *self
-                .players
-                .entry(DeviceId::Gamepad(event.id))
-                .or_insert_with(|| self.game.spawn_player());
+            let device = DeviceId::Gamepad(event.id);
 
             match event.event {
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    if let Some(action) = button_nav(button) {
+                        self.ui_actions.push((device, action));
+                        self.input(device, InputAction::Nav(action));
+                    }
+                }
+                gilrs::EventType::ButtonRepeated(button, _) => {
+                    self.ui_actions
+                        .extend(button_nav(button).map(|action| (device, action)));
+                }
                 gilrs::EventType::AxisChanged(axis, mut amount, ..) => {
+                    self.stick_nav(event.id, axis, amount);
+
                     if amount.abs() < 0.1 {
                         amount = 0.0;
                     }
                     match axis {
                         gilrs::Axis::LeftStickX | gilrs::Axis::DPadX => {
-                            self.game.handle_input(InputEvent {
-                                id: player,
-                                input: Input::X(amount),
-                            });
+                            self.input(device, InputAction::MoveX(amount));
                         }
                         gilrs::Axis::LeftStickY | gilrs::Axis::DPadY => {
-                            self.game.handle_input(InputEvent {
-                                id: player,
-                                input: Input::Y(amount),
-                            });
+                            self.input(device, InputAction::MoveY(amount));
                         }
                         _ => {}
                     }
                 }
                 gilrs::EventType::Connected => {}
-                gilrs::EventType::Disconnected => {}
+                gilrs::EventType::Disconnected => {
+                    self.nav_sticks.remove(&event.id);
+                    self.input(device, InputAction::Disconnected);
+                }
                 _ => {}
             }
         }
@@ -140,13 +252,18 @@ impl ApplicationHandler<AppEvent> for App {
 
     fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
         match event {
-            AppEvent::RendererCreated(renderer) => {
-                renderer.window.request_redraw();
-                self.game.resize(
-                    renderer.window.inner_size().width,
-                    renderer.window.inner_size().height,
-                );
-                self.renderer = Some(renderer);
+            AppEvent::RendererCreated(mut renderer) => {
+                renderer.set_post_process(self.applied.post_process);
+                if let Err(e) = renderer.set_msaa_samples(self.applied.msaa_samples) {
+                    log::error!("Unable to set up MSAA: {e:#}");
+                }
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                    let size = window.inner_size();
+                    self.states
+                        .resize(&mut self.context, size.width, size.height);
+                }
+                self.renderer = Some(*renderer);
             }
             AppEvent::RendererFailed => event_loop.exit(),
         }
@@ -158,55 +275,39 @@ impl ApplicationHandler<AppEvent> for App {
         _device_id: WinitDeviceId,
         event: DeviceEvent,
     ) {
-        let id = *self
-            .players
-            .entry(DeviceId::Winit(_device_id))
-            .or_insert_with(|| self.game.spawn_player());
+        let device = DeviceId::Keyboard;
 
-        match event {
-            DeviceEvent::Key(RawKeyEvent {
-                physical_key: PhysicalKey::Code(key),
-                state,
-            }) => {
-                const W: usize = 0;
-                const A: usize = 1;
-                const S: usize = 2;
-                const D: usize = 3;
-
-                let amount = if state.is_pressed() { 1.0 } else { 0.0 };
-                match key {
-                    KeyCode::KeyW | KeyCode::ArrowUp => {
-                        self.wasd[W] = amount;
-                        self.game.handle_input(InputEvent {
-                            id,
-                            input: Input::Y(self.wasd[W] - self.wasd[S]),
-                        });
-                    }
-                    KeyCode::KeyA | KeyCode::ArrowLeft => {
-                        self.wasd[A] = amount;
-                        self.game.handle_input(InputEvent {
-                            id,
-                            input: Input::X(self.wasd[D] - self.wasd[A]),
-                        });
-                    }
-                    KeyCode::KeyS | KeyCode::ArrowDown => {
-                        self.wasd[S] = amount;
-                        self.game.handle_input(InputEvent {
-                            id,
-                            input: Input::Y(self.wasd[W] - self.wasd[S]),
-                        });
-                    }
-                    KeyCode::KeyD | KeyCode::ArrowRight => {
-                        self.wasd[D] = amount;
-                        self.game.handle_input(InputEvent {
-                            id,
-                            input: Input::X(self.wasd[D] - self.wasd[A]),
-                        });
-                    }
-                    _ => {}
+        if let DeviceEvent::Key(RawKeyEvent {
+            physical_key: PhysicalKey::Code(key),
+            state,
+        }) = event
+        {
+            if is_fullscreen_toggle(key, self.modifiers) {
+                return;
+            }
+
+            // Indices into `Bindings::directions`
+            const W: usize = 0;
+            const A: usize = 1;
+            const S: usize = 2;
+            const D: usize = 3;
+
+            if state.is_pressed() {
+                if let Some(action) = key_nav(key) {
+                    self.input(device, InputAction::Nav(action));
                 }
+                self.input(device, InputAction::Key(key));
             }
-            _ => {}
+
+            let Some(direction) = self.context.settings.bindings.direction(key) else {
+                return;
+            };
+            self.wasd[direction] = if state.is_pressed() { 1.0 } else { 0.0 };
+            let action = match direction {
+                W | S => InputAction::MoveY(self.wasd[W] - self.wasd[S]),
+                _ => InputAction::MoveX(self.wasd[D] - self.wasd[A]),
+            };
+            self.input(device, action);
         }
     }
 
@@ -216,20 +317,84 @@ impl ApplicationHandler<AppEvent> for App {
         _window_id: winit::window::WindowId,
         event: WindowEvent,
     ) {
-        let renderer = if let Some(renderer) = &mut self.renderer {
-            renderer
-        } else {
+        let (Some(renderer), Some(window)) = (&mut self.renderer, &self.window) else {
             return;
         };
 
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if state.is_pressed() && is_fullscreen_toggle(key, self.modifiers) => {
+                self.toggle_fullscreen();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F12),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if state.is_pressed() => {
+                renderer.capture_next_frame();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F3),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if state.is_pressed() => {
+                self.context.debug_draw = !self.context.debug_draw;
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        ..
+                    },
+                ..
+            } if state.is_pressed() => {
+                self.ui_actions
+                    .extend(key_nav(key).map(|action| (DeviceId::Keyboard, action)));
+            }
+            WindowEvent::Focused(false) => {
+                self.states.focus_lost(&mut self.context);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.context.scale_factor = scale_factor as f32;
+            }
             WindowEvent::Resized(size) => {
                 renderer.resize(size.width, size.height);
-                self.game.resize(size.width, size.height);
+                self.states
+                    .resize(&mut self.context, size.width, size.height);
             }
             WindowEvent::RedrawRequested => {
-                renderer.window.request_redraw();
+                match self.context.settings.frame_interval() {
+                    Some(interval) => {
+                        // `new_events` asks for the next frame once it's time
+                        self.next_frame = (self.next_frame + interval).max(Instant::now());
+                        event_loop.set_control_flow(ControlFlow::WaitUntil(self.next_frame));
+                    }
+                    None => {
+                        event_loop.set_control_flow(ControlFlow::Wait);
+                        window.request_redraw();
+                    }
+                }
 
                 let dt = self.game_timer.elapsed();
                 self.accumulator += dt;
@@ -238,14 +403,130 @@ impl ApplicationHandler<AppEvent> for App {
                 const TICK_RATE: Duration = Duration::from_millis(16);
                 while self.accumulator > TICK_RATE {
                     self.accumulator -= TICK_RATE;
-                    self.game.tick(TICK_RATE);
+                    self.states.update(&mut self.context, TICK_RATE);
+                }
+
+                let size = window.inner_size();
+                let screen = Rect::new(
+                    glam::Vec2::ZERO,
+                    glam::vec2(size.width as f32, size.height as f32),
+                );
+                let ui = self.states.ui(&mut self.context, &self.ui_actions, screen);
+                self.ui_actions.clear();
+                if self.context.settings != self.applied {
+                    apply_settings(renderer, window, &self.applied, &self.context.settings);
+                    self.applied = self.context.settings.clone();
+                    if self.applied.window_mode != WindowMode::Windowed {
+                        self.last_fullscreen = self.applied.window_mode;
+                    }
                 }
 
-                if !renderer.render(&self.game) {
+                if !self.states.is_running() || !renderer.render(self.states.game(), &ui) {
                     event_loop.exit();
                 }
+                match renderer.take_screenshot() {
+                    Some(Ok(image)) => save_screenshot(image),
+                    Some(Err(e)) => log::error!("Unable to take a screenshot: {e:#}"),
+                    None => {}
+                }
             }
             _ => {}
         }
     }
 }
+
+/// Only touches the window and renderer for settings that changed.
+fn apply_settings(renderer: &mut Renderer, window: &Window, old: &Settings, new: &Settings) {
+    if new.window_mode != old.window_mode || new.resolution != old.resolution {
+        window.set_fullscreen(fullscreen(new, window.current_monitor()));
+        if new.window_mode == WindowMode::Windowed {
+            let [width, height] = new.resolution;
+            // Resized events take care of the rest
+            let _ = window.request_inner_size(PhysicalSize::new(width, height));
+        }
+    }
+    if new.surface_options() != old.surface_options() {
+        renderer.set_surface_options(new.surface_options());
+    }
+    if new.post_process != old.post_process {
+        renderer.set_post_process(new.post_process);
+    }
+    if new.msaa_samples != old.msaa_samples
+        && let Err(e) = renderer.set_msaa_samples(new.msaa_samples)
+    {
+        log::error!("Unable to set up MSAA: {e:#}");
+    }
+}
+
+/// Exclusive fullscreen uses the monitor's video mode closest to the
+/// resolution setting, or borderless if there aren't any.
+fn fullscreen(settings: &Settings, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match settings.window_mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Fullscreen => {
+            let [width, height] = settings.resolution;
+            let video_mode = monitor.as_ref().and_then(|monitor| {
+                monitor.video_modes().min_by_key(|mode| {
+                    let size = mode.size();
+                    (
+                        size.width.abs_diff(width) + size.height.abs_diff(height),
+                        std::cmp::Reverse(mode.refresh_rate_millihertz()),
+                    )
+                })
+            });
+            match video_mode {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => Some(Fullscreen::Borderless(monitor)),
+            }
+        }
+    }
+}
+
+/// Encodes and writes the image on another thread so the game doesn't hitch.
+fn save_screenshot(image: image::RgbaImage) {
+    std::thread::spawn(move || {
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = std::path::Path::new(SCREENSHOT_DIR).join(format!("screenshot-{time}.png"));
+        let result = std::fs::create_dir_all(SCREENSHOT_DIR)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(image.save(&path)?));
+        match result {
+            Ok(()) => log::info!("Saved screenshot to {}", path.display()),
+            Err(e) => log::error!("Unable to save {}: {e:#}", path.display()),
+        }
+    });
+}
+
+fn is_fullscreen_toggle(key: KeyCode, modifiers: ModifiersState) -> bool {
+    modifiers.alt_key() && matches!(key, KeyCode::Enter | KeyCode::NumpadEnter)
+}
+
+/// Menus are navigated with the arrow keys only, letters are free to be
+/// bound to movement.
+fn key_nav(key: KeyCode) -> Option<NavAction> {
+    match key {
+        KeyCode::ArrowUp => Some(NavAction::Up),
+        KeyCode::ArrowDown => Some(NavAction::Down),
+        KeyCode::ArrowLeft => Some(NavAction::Left),
+        KeyCode::ArrowRight => Some(NavAction::Right),
+        KeyCode::Enter | KeyCode::NumpadEnter | KeyCode::Space => Some(NavAction::Accept),
+        KeyCode::Escape | KeyCode::Backspace => Some(NavAction::Back),
+        _ => None,
+    }
+}
+
+fn button_nav(button: gilrs::Button) -> Option<NavAction> {
+    match button {
+        gilrs::Button::DPadUp => Some(NavAction::Up),
+        gilrs::Button::DPadDown => Some(NavAction::Down),
+        gilrs::Button::DPadLeft => Some(NavAction::Left),
+        gilrs::Button::DPadRight => Some(NavAction::Right),
+        gilrs::Button::South => Some(NavAction::Accept),
+        gilrs::Button::East => Some(NavAction::Back),
+        _ => None,
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayMode {
+    /// Stops on the last frame
+    Once,
+    #[default]
+    Loop,
+    /// Plays forwards then backwards, without repeating the end frames
+    PingPong,
+}
+
+/// An image split into a grid of equally sized frames, numbered left to right
+/// then top to bottom.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SpriteSheet {
+    pub name: String,
+    pub image: String,
+    pub frame_width: u32,
+    pub frame_height: u32,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct Frame {
+    /// Frame in the clip's sprite sheet
+    pub index: usize,
+    /// Seconds
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AnimationClip {
+    pub sheet: String,
+    #[serde(default)]
+    pub mode: PlayMode,
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClipId(usize);
+
+impl ClipId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct AnimationData {
+    sheets: Vec<SpriteSheet>,
+    clips: HashMap<String, AnimationClip>,
+}
+
+/// Sprite sheets and the clips that play them, loaded from `animations.json`.
+#[derive(Debug)]
+pub struct AnimationLibrary {
+    sheets: Vec<SpriteSheet>,
+    clips: Vec<AnimationClip>,
+    names: HashMap<String, ClipId>,
+}
+
+impl AnimationLibrary {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let data: AnimationData = serde_json::from_str(json)?;
+
+        for (i, sheet) in data.sheets.iter().enumerate() {
+            if sheet.frame_width == 0 || sheet.frame_height == 0 {
+                anyhow::bail!("Sheet {} has an empty frame size", sheet.name);
+            }
+            if data.sheets[..i]
+                .iter()
+                .any(|other| other.name == sheet.name)
+            {
+                anyhow::bail!("There's more than one sheet named {}", sheet.name);
+            }
+        }
+
+        let mut clips = Vec::with_capacity(data.clips.len());
+        let mut names = HashMap::with_capacity(data.clips.len());
+
+        for (name, clip) in data.clips {
+            if !data.sheets.iter().any(|sheet| sheet.name == clip.sheet) {
+                anyhow::bail!("Clip {name} uses unknown sheet {}", clip.sheet);
+            }
+            if clip.frames.is_empty() {
+                anyhow::bail!("Clip {name} has no frames");
+            }
+            if clip.frames.iter().any(|frame| frame.duration <= 0.0) {
+                anyhow::bail!("Clip {name} has a frame with no duration");
+            }
+            names.insert(name, ClipId(clips.len()));
+            clips.push(clip);
+        }
+
+        Ok(Self {
+            sheets: data.sheets,
+            clips,
+            names,
+        })
+    }
+
+    pub fn sheets(&self) -> &[SpriteSheet] {
+        &self.sheets
+    }
+
+    pub fn clip_id(&self, name: &str) -> Option<ClipId> {
+        self.names.get(name).copied()
+    }
+
+    /// Checks the clips playing `sheet` only use frames that fit in its
+    /// image, which is `width` by `height` pixels.
+    pub fn check_sheet_size(
+        &self,
+        sheet: &SpriteSheet,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<()> {
+        let frames = (width / sheet.frame_width) as usize * (height / sheet.frame_height) as usize;
+        for (name, &id) in &self.names {
+            let clip = &self.clips[id.0];
+            if clip.sheet != sheet.name {
+                continue;
+            }
+            if let Some(frame) = clip.frames.iter().find(|frame| frame.index >= frames) {
+                anyhow::bail!(
+                    "Clip {name} uses frame {} but sheet {} only has {frames}",
+                    frame.index,
+                    sheet.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clip(&self, id: ClipId) -> &AnimationClip {
+        &self.clips[id.0]
+    }
+
+    /// Clips in [`ClipId`] order.
+    pub fn clips(&self) -> &[AnimationClip] {
+        &self.clips
+    }
+}
+
+/// Playback state of a clip for one entity.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    clip: ClipId,
+    frame: usize,
+    elapsed: f32,
+    reverse: bool,
+    finished: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(clip: ClipId) -> Self {
+        Self {
+            clip,
+            frame: 0,
+            elapsed: 0.0,
+            reverse: false,
+            finished: false,
+        }
+    }
+
+    /// Switches to `clip`, restarting it unless it's already playing.
+    pub fn play(&mut self, clip: ClipId) {
+        if self.clip != clip {
+            *self = Self::new(clip);
+        }
+    }
+
+    pub fn clip(&self) -> ClipId {
+        self.clip
+    }
+
+    /// Index into the clip's frames, not the sprite sheet.
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    pub fn tick(&mut self, library: &AnimationLibrary, dt: f32) {
+        if self.finished {
+            return;
+        }
+
+        let clip = library.clip(self.clip);
+        let last = clip.frames.len() - 1;
+
+        self.elapsed += dt;
+        while self.elapsed >= clip.frames[self.frame].duration {
+            self.elapsed -= clip.frames[self.frame].duration;
+            match clip.mode {
+                PlayMode::Once => {
+                    if self.frame == last {
+                        self.finished = true;
+                        self.elapsed = 0.0;
+                        return;
+                    }
+                    self.frame += 1;
+                }
+                PlayMode::Loop => self.frame = (self.frame + 1) % clip.frames.len(),
+                PlayMode::PingPong => {
+                    if last == 0 {
+                        continue;
+                    }
+                    if self.reverse && self.frame == 0 {
+                        self.reverse = false;
+                    } else if !self.reverse && self.frame == last {
+                        self.reverse = true;
+                    }
+                    if self.reverse {
+                        self.frame -= 1;
+                    } else {
+                        self.frame += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "sheets": [
+            { "name": "sheet", "image": "sheet.png", "frame_width": 16, "frame_height": 16 }
+        ],
+        "clips": {
+            "once": { "sheet": "sheet", "mode": "once", "frames": [
+                { "index": 0, "duration": 1.0 },
+                { "index": 1, "duration": 1.0 },
+                { "index": 2, "duration": 1.0 }
+            ] },
+            "loop": { "sheet": "sheet", "frames": [
+                { "index": 0, "duration": 1.0 },
+                { "index": 1, "duration": 1.0 },
+                { "index": 2, "duration": 1.0 }
+            ] },
+            "ping_pong": { "sheet": "sheet", "mode": "ping_pong", "frames": [
+                { "index": 0, "duration": 1.0 },
+                { "index": 1, "duration": 1.0 },
+                { "index": 2, "duration": 1.0 }
+            ] },
+            "still": { "sheet": "sheet", "mode": "ping_pong", "frames": [
+                { "index": 3, "duration": 1.0 }
+            ] }
+        }
+    }"#;
+
+    /// Frame indices after each of `ticks` one second ticks.
+    fn play(clip: &str, ticks: usize) -> Vec<usize> {
+        let library = AnimationLibrary::from_json(JSON).unwrap();
+        let mut player = AnimationPlayer::new(library.clip_id(clip).unwrap());
+        (0..ticks)
+            .map(|_| {
+                player.tick(&library, 1.0);
+                player.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        assert_eq!(play("once", 5), [1, 2, 2, 2, 2]);
+
+        let library = AnimationLibrary::from_json(JSON).unwrap();
+        let mut player = AnimationPlayer::new(library.clip_id("once").unwrap());
+        player.tick(&library, 2.5);
+        assert!(!player.finished);
+        player.tick(&library, 0.5);
+        assert!(player.finished);
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        assert_eq!(play("loop", 7), [1, 2, 0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn ping_pong_doesnt_repeat_the_ends() {
+        assert_eq!(play("ping_pong", 8), [1, 2, 1, 0, 1, 2, 1, 0]);
+        assert_eq!(play("still", 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn long_ticks_skip_frames() {
+        let library = AnimationLibrary::from_json(JSON).unwrap();
+        let mut player = AnimationPlayer::new(library.clip_id("loop").unwrap());
+        player.tick(&library, 4.5);
+        assert_eq!(player.frame_index(), 1);
+        player.tick(&library, 0.5);
+        assert_eq!(player.frame_index(), 2);
+    }
+
+    #[test]
+    fn every_sheet_needs_a_frame_size() {
+        let json = r#"{
+            "sheets": [
+                { "name": "used", "image": "a.png", "frame_width": 16, "frame_height": 16 },
+                { "name": "unused", "image": "b.png", "frame_width": 0, "frame_height": 16 }
+            ],
+            "clips": {
+                "clip": { "sheet": "used", "frames": [{ "index": 0, "duration": 1.0 }] }
+            }
+        }"#;
+        let error = AnimationLibrary::from_json(json).unwrap_err();
+        assert!(error.to_string().contains("unused"));
+    }
+
+    #[test]
+    fn sheet_names_are_unique() {
+        let json = r#"{
+            "sheets": [
+                { "name": "sheet", "image": "a.png", "frame_width": 16, "frame_height": 16 },
+                { "name": "sheet", "image": "b.png", "frame_width": 8, "frame_height": 8 }
+            ],
+            "clips": {}
+        }"#;
+        let error = AnimationLibrary::from_json(json).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("more than one sheet named sheet")
+        );
+    }
+
+    #[test]
+    fn frames_have_to_fit_in_the_sheet() {
+        let library = AnimationLibrary::from_json(JSON).unwrap();
+        let sheet = &library.sheets()[0];
+
+        // 4 frames are used, indices 0 to 3
+        assert!(library.check_sheet_size(sheet, 64, 16).is_ok());
+        assert!(library.check_sheet_size(sheet, 32, 32).is_ok());
+        assert!(library.check_sheet_size(sheet, 48, 16).is_err());
+        assert!(library.check_sheet_size(sheet, 8, 8).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! Shake and zoom punches layered over wherever a camera is pointed, so
+//! gameplay can ask for an impact without moving the camera itself.
+
+use crate::game::camera::spring;
+
+#[derive(Debug, Clone)]
+pub struct CameraEffects {
+    /// Furthest the shake moves the camera, in world units
+    pub max_offset: f32,
+    /// Furthest the shake rolls the camera, in radians
+    pub max_angle: f32,
+    /// Trauma lost per second
+    pub trauma_decay: f32,
+    /// How quickly the shake wobbles, in noise samples per second
+    pub shake_frequency: f32,
+    /// Spring frequency pulling zoom punches back, in radians per second
+    pub punch_frequency: f32,
+    /// From 0 to 1, the shake grows with its square
+    trauma: f32,
+    time: f32,
+    punch: f32,
+    punch_velocity: f32,
+    offset: glam::Vec2,
+    angle: f32,
+}
+
+impl Default for CameraEffects {
+    fn default() -> Self {
+        Self {
+            max_offset: 8.0,
+            max_angle: 0.05,
+            trauma_decay: 1.5,
+            shake_frequency: 20.0,
+            punch_frequency: 12.0,
+            trauma: 0.0,
+            time: 0.0,
+            punch: 0.0,
+            punch_velocity: 0.0,
+            offset: glam::Vec2::ZERO,
+            angle: 0.0,
+        }
+    }
+}
+
+impl CameraEffects {
+    /// Shakes the camera harder. Small hits add up, to at most 1.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// Zooms in by `amount`, eg. 0.1 for 10%, then springs back out.
+    pub fn punch(&mut self, amount: f32) {
+        self.punch += amount;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.trauma_decay * dt).max(0.0);
+        spring(
+            &mut self.punch,
+            &mut self.punch_velocity,
+            0.0,
+            self.punch_frequency,
+            dt,
+        );
+
+        // Separate noise per axis so the shake doesn't move diagonally
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.shake_frequency;
+        self.offset = glam::vec2(noise(0, t), noise(1, t)) * shake * self.max_offset;
+        self.angle = noise(2, t) * shake * self.max_angle;
+    }
+
+    /// Added to the camera's position.
+    pub fn offset(&self) -> glam::Vec2 {
+        self.offset
+    }
+
+    /// Added to the camera's rotation.
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// Multiplies the camera's zoom.
+    pub fn zoom(&self) -> f32 {
+        1.0 + self.punch
+    }
+}
+
+/// Smooth 1D value noise from -1 to 1.
+fn noise(seed: u32, t: f32) -> f32 {
+    let i = t.floor();
+    let f = t - i;
+    let a = hash(seed, i as i32);
+    let b = hash(seed, i as i32 + 1);
+    a + (b - a) * f * f * (3.0 - 2.0 * f)
+}
+
+fn hash(seed: u32, i: i32) -> f32 {
+    let mut x = (i as u32).wrapping_mul(0x9e37_79b9) ^ seed.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
@@ -0,0 +1,301 @@
+mod effects;
+
+pub use effects::CameraEffects;
+
+use crate::render::utils::Rect;
+
+pub trait Camera {
+    fn view(&self) -> glam::Mat4;
+    fn proj(&self) -> glam::Mat4;
+    /// Counterclockwise roll in radians, which `view` should include.
+    fn rotation(&self) -> f32 {
+        0.0
+    }
+    fn view_proj(&self) -> glam::Mat4 {
+        self.proj() * self.view()
+    }
+}
+
+pub struct Camera2d {
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    pub(crate) position: glam::Vec2,
+    /// Above 1 shows less than `width` by `height`, below 1 shows more
+    pub(crate) zoom: f32,
+    pub(crate) rotation: f32,
+    /// Applied on top of the position, zoom and rotation
+    pub(crate) effects: CameraEffects,
+}
+
+impl Camera2d {
+    pub fn new(width: f32, height: f32, position: glam::Vec2) -> Self {
+        Self {
+            width,
+            height,
+            position,
+            zoom: 1.0,
+            rotation: 0.0,
+            effects: CameraEffects::default(),
+        }
+    }
+
+    /// Eases toward `target`, covering the same fraction of the distance
+    /// every second whatever the frame rate.
+    pub fn follow(&mut self, target: glam::Vec2, rate: f32, dt: f32) {
+        let t = 1.0 - (-rate * dt).exp();
+        self.position = self.position.lerp(target, t);
+    }
+}
+
+impl Camera for Camera2d {
+    fn view(&self) -> glam::Mat4 {
+        let position = self.position + self.effects.offset();
+        let translation = glam::vec3(-position.x, -position.y, 0.0);
+        glam::Mat4::from_rotation_z(-self.rotation()) * glam::Mat4::from_translation(translation)
+    }
+
+    fn proj(&self) -> glam::Mat4 {
+        let zoom = self.zoom * self.effects.zoom();
+        let hx = self.width * 0.5 / zoom;
+        let hy = self.height * 0.5 / zoom;
+        // `view` already moved the camera to the origin
+        glam::Mat4::orthographic_rh(-hx, hx, -hy, hy, 0.0, 1.0)
+    }
+
+    fn rotation(&self) -> f32 {
+        self.rotation + self.effects.angle()
+    }
+}
+
+/// How the screen is divided between players.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum SplitScreen {
+    /// One camera for everyone
+    #[default]
+    Shared,
+    /// Two views side by side
+    TwoHorizontal,
+    /// Two views, one above the other
+    TwoVertical,
+    /// Four views, one in each corner
+    Quadrants,
+}
+
+impl SplitScreen {
+    pub const ALL: [Self; 4] = [
+        Self::Shared,
+        Self::TwoHorizontal,
+        Self::TwoVertical,
+        Self::Quadrants,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Shared => "Off",
+            Self::TwoHorizontal => "2 horizontal",
+            Self::TwoVertical => "2 vertical",
+            Self::Quadrants => "4 quadrants",
+        }
+    }
+
+    /// Parts of the screen each view covers, from 0 to 1 with the origin in
+    /// the top left.
+    pub fn viewports(self) -> Vec<Rect> {
+        let rect = |x: f32, y: f32, w: f32, h: f32| {
+            Rect::from_pos_size(glam::vec2(x, y), glam::vec2(w, h))
+        };
+        match self {
+            Self::Shared => vec![rect(0.0, 0.0, 1.0, 1.0)],
+            Self::TwoHorizontal => vec![rect(0.0, 0.0, 0.5, 1.0), rect(0.5, 0.0, 0.5, 1.0)],
+            Self::TwoVertical => vec![rect(0.0, 0.0, 1.0, 0.5), rect(0.0, 0.5, 1.0, 0.5)],
+            Self::Quadrants => vec![
+                rect(0.0, 0.0, 0.5, 0.5),
+                rect(0.5, 0.0, 0.5, 0.5),
+                rect(0.0, 0.5, 0.5, 0.5),
+                rect(0.5, 0.5, 0.5, 0.5),
+            ],
+        }
+    }
+}
+
+/// How a view fits its virtual size into the pixels it's given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum ScaleMode {
+    /// Shows at least the virtual size, and more along whichever axis has
+    /// room to spare
+    #[default]
+    Expand,
+    /// Shows exactly the virtual size, with black bars on the sides or top
+    /// and bottom
+    Letterbox,
+}
+
+impl ScaleMode {
+    pub const ALL: [Self; 2] = [Self::Expand, Self::Letterbox];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Expand => "Expand",
+            Self::Letterbox => "Letterbox",
+        }
+    }
+
+    /// Returns the part of `area` to draw to and the camera size to draw
+    /// with, for a view showing `virtual_size` world units. Empty areas, eg.
+    /// from a minimized window, get the virtual size.
+    pub fn fit(self, virtual_size: glam::Vec2, area: Rect) -> (Rect, glam::Vec2) {
+        if area.size().cmple(glam::Vec2::ZERO).any() {
+            return (area, virtual_size);
+        }
+        let pixels_per_unit = (area.size() / virtual_size).min_element();
+        match self {
+            Self::Expand => (area, area.size() / pixels_per_unit),
+            Self::Letterbox => {
+                let size = virtual_size * pixels_per_unit;
+                let min = area.center() - size * 0.5;
+                (Rect::from_pos_size(min, size), virtual_size)
+            }
+        }
+    }
+}
+
+/// Moves `value` toward `target` as a critically damped spring, which gets
+/// there as fast as possible without overshooting. `frequency` is in radians
+/// per second, higher is stiffer.
+pub fn spring<T>(value: &mut T, velocity: &mut T, target: T, frequency: f32, dt: f32)
+where
+    T: Copy
+        + std::ops::Add<Output = T>
+        + std::ops::Sub<Output = T>
+        + std::ops::Mul<f32, Output = T>,
+{
+    // Closed form approximation from Game Programming Gems 4, stable for
+    // any dt
+    let x = frequency * dt;
+    let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+    let offset = *value - target;
+    let temp = (*velocity + offset * frequency) * dt;
+    *velocity = (*velocity - temp * frequency) * decay;
+    *value = target + (offset + temp) * decay;
+}
+
+/// Pans and zooms a shared camera to keep everyone in view.
+#[derive(Debug, Clone)]
+pub struct FramingCamera {
+    /// Space kept around the framed rects, in world units
+    pub padding: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Spring frequency in radians per second
+    pub frequency: f32,
+    /// The camera doesn't show past these edges unless it has to
+    pub bounds: Option<Rect>,
+    velocity: glam::Vec2,
+    zoom_velocity: f32,
+}
+
+impl FramingCamera {
+    pub fn new(bounds: Option<Rect>) -> Self {
+        Self {
+            padding: 30.0,
+            min_zoom: 0.5,
+            max_zoom: 2.0,
+            frequency: 4.0,
+            bounds,
+            velocity: glam::Vec2::ZERO,
+            zoom_velocity: 0.0,
+        }
+    }
+
+    /// Springs `camera` toward framing all of `targets`, or the middle of
+    /// the bounds if there aren't any.
+    pub fn update(&mut self, camera: &mut Camera2d, targets: &[Rect], dt: f32) {
+        let size = glam::vec2(camera.width, camera.height);
+        let (mut position, zoom) = match targets.split_first() {
+            Some((first, rest)) => {
+                let framed = rest
+                    .iter()
+                    .fold(*first, |framed, rect| framed.union(rect))
+                    .inset(-self.padding);
+                let zoom = (size / framed.size().max(glam::Vec2::splat(1.0))).min_element();
+                (framed.center(), zoom.clamp(self.min_zoom, self.max_zoom))
+            }
+            None => (
+                self.bounds
+                    .map_or(glam::Vec2::ZERO, |bounds| bounds.center()),
+                1.0,
+            ),
+        };
+
+        // Clamped against the target zoom so the camera doesn't drift while
+        // zooming
+        if let Some(bounds) = self.bounds {
+            let half = size / zoom * 0.5;
+            for axis in 0..2 {
+                let (min, max) = (bounds.min[axis] + half[axis], bounds.max[axis] - half[axis]);
+                position[axis] = if min <= max {
+                    position[axis].clamp(min, max)
+                } else {
+                    bounds.center()[axis]
+                };
+            }
+        }
+
+        spring(
+            &mut camera.position,
+            &mut self.velocity,
+            position,
+            self.frequency,
+            dt,
+        );
+        spring(
+            &mut camera.zoom,
+            &mut self.zoom_velocity,
+            zoom,
+            self.frequency,
+            dt,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIRTUAL_SIZE: glam::Vec2 = glam::vec2(320.0, 180.0);
+
+    #[test]
+    fn expand_shows_more_of_the_longer_axis() {
+        let area = Rect::new(glam::Vec2::ZERO, glam::vec2(1000.0, 360.0));
+        let (viewport, size) = ScaleMode::Expand.fit(VIRTUAL_SIZE, area);
+        assert_eq!(viewport, area);
+        assert_eq!(size, glam::vec2(500.0, 180.0));
+    }
+
+    #[test]
+    fn letterbox_centers_the_virtual_size() {
+        let area = Rect::new(glam::Vec2::ZERO, glam::vec2(1000.0, 360.0));
+        let (viewport, size) = ScaleMode::Letterbox.fit(VIRTUAL_SIZE, area);
+        assert_eq!(size, VIRTUAL_SIZE);
+        assert_eq!(
+            viewport,
+            Rect::new(glam::vec2(180.0, 0.0), glam::vec2(820.0, 360.0))
+        );
+    }
+
+    #[test]
+    fn empty_areas_stay_finite() {
+        for mode in ScaleMode::ALL {
+            for size in [
+                glam::Vec2::ZERO,
+                glam::vec2(100.0, 0.0),
+                glam::vec2(0.0, 100.0),
+            ] {
+                let area = Rect::from_pos_size(glam::vec2(10.0, 10.0), size);
+                let (viewport, camera) = mode.fit(VIRTUAL_SIZE, area);
+                assert!(viewport.min.is_finite() && viewport.max.is_finite());
+                assert!(camera.is_finite() && camera.cmpgt(glam::Vec2::ZERO).all());
+            }
+        }
+    }
+}
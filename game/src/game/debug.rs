@@ -0,0 +1,111 @@
+//! Lines and labels in world space for seeing what the game is doing, drawn
+//! over everything else when debug drawing is on.
+//!
+//! Gameplay code can call these anywhere during a tick, they do nothing while
+//! debug drawing is off. Everything is cleared at the start of the next tick.
+
+use crate::render::utils::Rect;
+
+/// Segments used for circles
+const CIRCLE_SEGMENTS: usize = 24;
+/// Length of an arrow's head, as a fraction of the arrow
+const ARROW_HEAD: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DebugLine {
+    pub a: glam::Vec2,
+    pub b: glam::Vec2,
+    pub color: glam::Vec4,
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugText {
+    /// Where the text is centered
+    pub position: glam::Vec2,
+    pub text: String,
+    pub color: glam::Vec4,
+}
+
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    enabled: bool,
+    lines: Vec<DebugLine>,
+    texts: Vec<DebugText>,
+}
+
+impl DebugDraw {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turning it off also drops anything already drawn.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.texts.clear();
+    }
+
+    pub fn line(&mut self, a: glam::Vec2, b: glam::Vec2, color: glam::Vec4) {
+        if self.enabled {
+            self.lines.push(DebugLine { a, b, color });
+        }
+    }
+
+    pub fn circle(&mut self, center: glam::Vec2, radius: f32, color: glam::Vec4) {
+        if !self.enabled {
+            return;
+        }
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + glam::Vec2::from_angle(angle) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    pub fn rect(&mut self, rect: Rect, color: glam::Vec4) {
+        let corners = [
+            rect.min,
+            glam::vec2(rect.max.x, rect.min.y),
+            rect.max,
+            glam::vec2(rect.min.x, rect.max.y),
+        ];
+        for i in 0..corners.len() {
+            self.line(corners[i], corners[(i + 1) % corners.len()], color);
+        }
+    }
+
+    /// A line from `from` to `to` with a head at `to`.
+    pub fn arrow(&mut self, from: glam::Vec2, to: glam::Vec2, color: glam::Vec4) {
+        self.line(from, to, color);
+        let back = (from - to) * ARROW_HEAD;
+        for angle in [-0.5f32, 0.5] {
+            self.line(to, to + glam::Vec2::from_angle(angle).rotate(back), color);
+        }
+    }
+
+    pub fn text(&mut self, position: glam::Vec2, text: impl Into<String>, color: glam::Vec4) {
+        if self.enabled {
+            self.texts.push(DebugText {
+                position,
+                text: text.into(),
+                color,
+            });
+        }
+    }
+
+    pub fn lines(&self) -> &[DebugLine] {
+        &self.lines
+    }
+
+    pub fn texts(&self) -> &[DebugText] {
+        &self.texts
+    }
+}
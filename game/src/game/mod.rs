@@ -1,11 +1,65 @@
+pub mod animation;
 pub mod camera;
+pub mod debug;
+pub mod particles;
 
-use std::{
-    collections::HashSet,
-    time::Duration,
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    game::{
+        animation::{AnimationLibrary, AnimationPlayer, ClipId},
+        camera::{Camera, Camera2d, FramingCamera, ScaleMode, SplitScreen},
+        debug::DebugDraw,
+        particles::{Emitter, ParticleConfig, ParticleSystem},
+    },
+    render::{PLAYER_COLORS, utils::Rect},
 };
 
-use crate::game::camera::Camera2d;
+/// Half the width of the square pickups spawn in
+const ARENA_EXTENT: f32 = 100.0;
+/// How far out the shared camera can show, a little past the pickups
+const CAMERA_EXTENT: f32 = ARENA_EXTENT + 20.0;
+/// Sparks where a pickup was collected
+const PICKUP_BURST: ParticleConfig = ParticleConfig {
+    rate: 0.0,
+    lifetime: (0.3, 0.6),
+    speed: (40.0, 120.0),
+    direction: 0.0,
+    spread: std::f32::consts::PI,
+    drag: 4.0,
+    color: (
+        glam::vec4(1.0, 0.9, 0.3, 1.0),
+        glam::vec4(1.0, 0.5, 0.0, 0.0),
+    ),
+    size: (2.5, 0.5),
+};
+const PICKUP_BURST_COUNT: usize = 24;
+/// Puffs left behind moving players, tinted with the player's color
+const PLAYER_TRAIL: ParticleConfig = ParticleConfig {
+    rate: 40.0,
+    lifetime: (0.3, 0.5),
+    speed: (5.0, 15.0),
+    direction: 0.0,
+    spread: 0.4,
+    drag: 2.0,
+    color: (glam::Vec4::ONE, glam::Vec4::ZERO),
+    size: (4.0, 1.0),
+};
+
+/// Space between the debug grid's lines
+const DEBUG_GRID_SPACING: f32 = 20.0;
+/// World units the whole screen shows, whatever the window size
+const VIRTUAL_SIZE: glam::Vec2 = glam::vec2(640.0, 360.0);
+
+/// How quickly split screen cameras catch up with their player, higher is
+/// stiffer
+const CAMERA_FOLLOW_RATE: f32 = 8.0;
+/// Camera shake from collecting a pickup, see [`camera::CameraEffects::add_trauma`]
+const PICKUP_TRAUMA: f32 = 0.3;
+/// Zoom from collecting a pickup, see [`camera::CameraEffects::punch`]
+const PICKUP_PUNCH: f32 = 0.05;
 
 pub struct InputEvent {
     pub(crate) id: PlayerId,
@@ -17,62 +71,175 @@ pub enum Input {
     Y(f32),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PlayerId(usize);
 
+/// Something that happened during the last tick, for effects to react to.
+#[derive(Debug, Clone, Copy)]
+pub enum GameEvent {
+    PickupCollected {
+        player: PlayerId,
+        position: glam::Vec2,
+    },
+}
+
 #[derive(Debug)]
 pub struct Player {
-    size: f32,
+    /// Radius of the circle used for collisions
+    pub(crate) size: f32,
     pub(crate) position: glam::Vec2,
     pub(crate) score: i32,
-    joystick: glam::Vec2,
+    pub(crate) joystick: glam::Vec2,
     speed: f32,
+    pub(crate) animation: AnimationPlayer,
+    /// Aimed away from where the player is going
+    trail: Emitter,
 }
 
 pub struct Pickup {
     pub(crate) position: glam::Vec2,
     value: i32,
+    pub(crate) animation: AnimationPlayer,
+}
+
+/// Clips the game switches between, looked up once from the library.
+struct Clips {
+    player_idle: ClipId,
+    player_move: ClipId,
+    pickup_spin: ClipId,
+}
+
+impl Clips {
+    fn new(animations: &AnimationLibrary) -> anyhow::Result<Self> {
+        let clip = |name| {
+            animations
+                .clip_id(name)
+                .with_context(|| format!("animations.json is missing the {name} clip"))
+        };
+        Ok(Self {
+            player_idle: clip("player_idle")?,
+            player_move: clip("player_move")?,
+            pickup_spin: clip("pickup_spin")?,
+        })
+    }
+}
+
+/// A camera and the part of the screen it's drawn to.
+pub struct View {
+    pub(crate) camera: Camera2d,
+    /// Part of the screen the view was given, from 0 to 1 with the origin
+    /// in the top left
+    pub(crate) area: Rect,
+    /// Part of `area` actually drawn to, smaller when letterboxed
+    pub(crate) viewport: Rect,
+    /// The player this view follows, if it's split screen
+    target: Option<PlayerId>,
+}
+
+impl View {
+    /// Pixel position of `world` on a screen of `screen_size` pixels, which
+    /// is also its position in UI space.
+    pub(crate) fn world_to_screen(&self, world: glam::Vec2, screen_size: glam::Vec2) -> glam::Vec2 {
+        let ndc = self.camera.view_proj().project_point3(world.extend(0.0));
+        // Clip space is y up, the screen is y down
+        let uv = glam::vec2(ndc.x + 1.0, 1.0 - ndc.y) * 0.5;
+        (self.viewport.min + uv * self.viewport.size()) * screen_size
+    }
+
+    /// The inverse of [`View::world_to_screen`].
+    pub(crate) fn screen_to_world(
+        &self,
+        screen: glam::Vec2,
+        screen_size: glam::Vec2,
+    ) -> glam::Vec2 {
+        let uv = (screen / screen_size - self.viewport.min) / self.viewport.size();
+        let ndc = glam::vec3(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0);
+        self.camera
+            .view_proj()
+            .inverse()
+            .project_point3(ndc)
+            .truncate()
+    }
 }
 
 pub struct Game {
     players: Vec<Player>,
     pickups: Vec<Pickup>,
-    camera: Camera2d,
-    ui_camera: Camera2d,
+    views: Vec<View>,
+    split_screen: SplitScreen,
+    scale_mode: ScaleMode,
+    /// Moves the view when the screen isn't split
+    framing: FramingCamera,
+    /// Window size in pixels
+    screen_size: glam::Vec2,
     pickup_timer: Duration,
     pickup_accumulator: Duration,
+    /// Cleared at the start of every tick
+    events: Vec<GameEvent>,
+    /// Cleared at the start of every tick
+    debug: DebugDraw,
+    particles: ParticleSystem,
+    animations: Arc<AnimationLibrary>,
+    clips: Clips,
 }
 
 impl Game {
-    pub fn new(pickup_timer: Duration) -> Self {
-        Self {
+    /// Fails if `animations` is missing a clip the game plays.
+    pub fn new(pickup_timer: Duration, animations: Arc<AnimationLibrary>) -> anyhow::Result<Self> {
+        let mut game = Self {
             players: Vec::new(),
             pickups: Vec::new(),
-            camera: Camera2d::new(1.0, 1.0, glam::Vec2::ZERO),
-            ui_camera: Camera2d::new(1.0, 1.0, glam::Vec2::ZERO),
+            views: Vec::new(),
+            split_screen: SplitScreen::Shared,
+            scale_mode: ScaleMode::default(),
+            framing: FramingCamera::new(Some(Rect::new(
+                glam::Vec2::splat(-CAMERA_EXTENT),
+                glam::Vec2::splat(CAMERA_EXTENT),
+            ))),
+            screen_size: glam::Vec2::ONE,
             pickup_timer,
             pickup_accumulator: Duration::ZERO,
-        }
+            events: Vec::new(),
+            debug: DebugDraw::default(),
+            particles: ParticleSystem::new(particles::DEFAULT_BUDGET),
+            clips: Clips::new(&animations)?,
+            animations,
+        };
+        game.rebuild_views();
+        Ok(game)
     }
 
     pub fn spawn_player(&mut self) -> PlayerId {
         let id = PlayerId(self.players.len());
+        let color = PLAYER_COLORS[id.0 % PLAYER_COLORS.len()];
+        let trail = ParticleConfig {
+            color: (color.with_w(0.6), color.with_w(0.0)),
+            ..PLAYER_TRAIL
+        };
         self.players.push(Player {
             size: 10.0,
             position: glam::vec2(0.0, 0.0),
             joystick: glam::vec2(0.0, 0.0),
             score: 0,
             speed: 100.0,
+            animation: AnimationPlayer::new(self.clips.player_idle),
+            trail: Emitter::new(trail),
         });
         id
     }
 
     pub fn tick(&mut self, dt: Duration) {
+        self.events.clear();
+        self.debug.clear();
         self.handle_spawn(dt);
 
         let dt = dt.as_secs_f32();
 
         self.handle_physics(dt);
+        self.handle_animation(dt);
+        self.handle_particles(dt);
+        self.handle_cameras(dt);
+        self.draw_debug();
     }
 
     fn handle_spawn(&mut self, dt: Duration) {
@@ -85,8 +252,9 @@ impl Game {
         while self.pickup_accumulator >= self.pickup_timer {
             self.pickup_accumulator -= self.pickup_timer;
             self.pickups.push(Pickup {
-                position: glam::vec2(rand::random(), rand::random()) * 200.0 - 100.0,
+                position: (glam::vec2(rand::random(), rand::random()) * 2.0 - 1.0) * ARENA_EXTENT,
                 value: 1,
+                animation: AnimationPlayer::new(self.clips.pickup_spin),
             });
         }
     }
@@ -127,7 +295,203 @@ impl Game {
 
             self.players[collision.player].score += self.pickups[collision.pickup].value;
 
-            let _ = self.pickups.swap_remove(collision.pickup);
+            let pickup = self.pickups.swap_remove(collision.pickup);
+            self.events.push(GameEvent::PickupCollected {
+                player: PlayerId(collision.player),
+                position: pickup.position,
+            });
+        }
+    }
+
+    fn handle_animation(&mut self, dt: f32) {
+        for player in &mut self.players {
+            let clip = if player.joystick.length_squared() > 0.01 {
+                self.clips.player_move
+            } else {
+                self.clips.player_idle
+            };
+            player.animation.play(clip);
+            player.animation.tick(&self.animations, dt);
+        }
+
+        for pickup in &mut self.pickups {
+            pickup.animation.tick(&self.animations, dt);
+        }
+    }
+
+    fn handle_particles(&mut self, dt: f32) {
+        for event in &self.events {
+            match *event {
+                GameEvent::PickupCollected { position, .. } => {
+                    self.particles
+                        .burst(&PICKUP_BURST, position, PICKUP_BURST_COUNT);
+                }
+            }
+        }
+
+        for player in &mut self.players {
+            if player.joystick.length_squared() > 0.01 {
+                let back = -player.joystick.normalize();
+                player.trail.config.direction = back.to_angle();
+                player.trail.update(
+                    &mut self.particles,
+                    player.position + back * player.size,
+                    dt,
+                );
+            }
+        }
+
+        self.particles.update(dt);
+    }
+
+    fn handle_cameras(&mut self, dt: f32) {
+        for event in &self.events {
+            match *event {
+                GameEvent::PickupCollected { player, .. } => {
+                    // Only the collector's view reacts in split screen
+                    for view in &mut self.views {
+                        if view.target.is_none_or(|target| target == player) {
+                            view.camera.effects.add_trauma(PICKUP_TRAUMA);
+                            view.camera.effects.punch(PICKUP_PUNCH);
+                        }
+                    }
+                }
+            }
+        }
+        for view in &mut self.views {
+            view.camera.effects.update(dt);
+        }
+
+        if self.split_screen == SplitScreen::Shared {
+            let players = self
+                .players
+                .iter()
+                .map(|player| {
+                    let extent = glam::Vec2::splat(player.size);
+                    Rect::new(player.position - extent, player.position + extent)
+                })
+                .collect::<Vec<_>>();
+            self.framing.update(&mut self.views[0].camera, &players, dt);
+            return;
+        }
+
+        for view in &mut self.views {
+            if let Some(player) = view.target.and_then(|id| self.players.get(id.0)) {
+                view.camera.follow(player.position, CAMERA_FOLLOW_RATE, dt);
+            }
+        }
+    }
+
+    /// Colliders, joysticks, pickup indices, the arena and what each view
+    /// shows.
+    fn draw_debug(&mut self) {
+        if !self.debug.is_enabled() {
+            return;
+        }
+
+        let grid = glam::vec4(1.0, 1.0, 1.0, 0.15);
+        let lines = (ARENA_EXTENT * 2.0 / DEBUG_GRID_SPACING) as i32;
+        for i in 0..=lines {
+            let offset = -ARENA_EXTENT + i as f32 * DEBUG_GRID_SPACING;
+            self.debug.line(
+                glam::vec2(offset, -ARENA_EXTENT),
+                glam::vec2(offset, ARENA_EXTENT),
+                grid,
+            );
+            self.debug.line(
+                glam::vec2(-ARENA_EXTENT, offset),
+                glam::vec2(ARENA_EXTENT, offset),
+                grid,
+            );
+        }
+        self.debug.rect(
+            Rect::new(
+                glam::Vec2::splat(-ARENA_EXTENT),
+                glam::Vec2::splat(ARENA_EXTENT),
+            ),
+            glam::vec4(1.0, 1.0, 1.0, 0.5),
+        );
+
+        // Corners of the viewports, so split screen views show each other
+        for view in &self.views {
+            let a = view.screen_to_world(view.viewport.min * self.screen_size, self.screen_size);
+            let b = view.screen_to_world(view.viewport.max * self.screen_size, self.screen_size);
+            self.debug.rect(
+                Rect::new(a.min(b), a.max(b)),
+                glam::vec4(0.0, 1.0, 1.0, 0.5),
+            );
+        }
+
+        for player in &self.players {
+            self.debug
+                .circle(player.position, player.size, glam::vec4(0.0, 1.0, 0.0, 1.0));
+            if player.joystick != glam::Vec2::ZERO {
+                self.debug.arrow(
+                    player.position,
+                    player.position + player.joystick * player.size * 2.0,
+                    glam::vec4(1.0, 1.0, 0.0, 1.0),
+                );
+            }
+        }
+
+        for (i, pickup) in self.pickups.iter().enumerate() {
+            self.debug.text(
+                pickup.position + glam::vec2(0.0, 8.0),
+                i.to_string(),
+                glam::Vec4::ONE,
+            );
+        }
+    }
+
+    /// Views follow players in order, so player 1 gets the first viewport.
+    pub(crate) fn set_split_screen(&mut self, split_screen: SplitScreen) {
+        if split_screen != self.split_screen {
+            self.split_screen = split_screen;
+            self.rebuild_views();
+        }
+    }
+
+    pub(crate) fn set_scale_mode(&mut self, scale_mode: ScaleMode) {
+        if scale_mode != self.scale_mode {
+            self.scale_mode = scale_mode;
+            self.resize_views();
+        }
+    }
+
+    fn rebuild_views(&mut self) {
+        let split = self.split_screen != SplitScreen::Shared;
+        self.views = self
+            .split_screen
+            .viewports()
+            .into_iter()
+            .enumerate()
+            .map(|(i, area)| {
+                let target = split.then_some(PlayerId(i));
+                let position = target
+                    .and_then(|id| self.players.get(id.0))
+                    .map_or(glam::Vec2::ZERO, |player| player.position);
+                View {
+                    camera: Camera2d::new(1.0, 1.0, position),
+                    area,
+                    viewport: area,
+                    target,
+                }
+            })
+            .collect();
+        self.resize_views();
+    }
+
+    /// Views show the same amount of the world in any window, split screen
+    /// views the share of [`VIRTUAL_SIZE`] matching their share of the
+    /// screen.
+    fn resize_views(&mut self) {
+        let screen = self.screen_size;
+        for view in &mut self.views {
+            let area = Rect::new(view.area.min * screen, view.area.max * screen);
+            let (viewport, size) = self.scale_mode.fit(VIRTUAL_SIZE * view.area.size(), area);
+            view.viewport = Rect::new(viewport.min / screen, viewport.max / screen);
+            view.camera.width = size.x;
+            view.camera.height = size.y;
         }
     }
 
@@ -139,15 +503,14 @@ impl Game {
         }
     }
 
+    /// Zero sizes, eg. from minimizing the window, keep the last size so
+    /// views don't end up with NaN cameras.
     pub(crate) fn resize(&mut self, width: u32, height: u32) {
-        let hw = width as f32 * 0.5;
-        let hh = height as f32 * 0.5;
-        self.camera.width = hw;
-        self.camera.height = hh;
-        self.ui_camera.width = width as f32;
-        self.ui_camera.height = -(height as f32);
-        self.ui_camera.position.x = self.ui_camera.width * 0.25;
-        self.ui_camera.position.y = self.ui_camera.height * -0.25;
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.screen_size = glam::vec2(width as f32, height as f32);
+        self.resize_views();
     }
 
     pub(crate) fn players(&self) -> &[Player] {
@@ -158,12 +521,24 @@ impl Game {
         &self.pickups
     }
 
-    pub(crate) fn active_camera(&self) -> &Camera2d {
-        &self.camera
+    pub(crate) fn views(&self) -> &[View] {
+        &self.views
     }
 
-    pub(crate) fn ui_camera(&self) -> &Camera2d {
-        &self.ui_camera
+    pub(crate) fn set_debug_draw(&mut self, enabled: bool) {
+        self.debug.set_enabled(enabled);
+    }
+
+    pub(crate) fn set_particle_budget(&mut self, budget: usize) {
+        self.particles.set_budget(budget);
+    }
+
+    pub(crate) fn particles(&self) -> &ParticleSystem {
+        &self.particles
+    }
+
+    pub(crate) fn debug(&self) -> &DebugDraw {
+        &self.debug
     }
 }
 
@@ -191,3 +566,96 @@ fn circle_point(
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game() -> Game {
+        let json =
+            std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/res/animations.json"))
+                .unwrap();
+        let animations = Arc::new(AnimationLibrary::from_json(&json).unwrap());
+        let mut game = Game::new(Duration::from_secs(1), animations).unwrap();
+        game.spawn_player();
+        game.spawn_player();
+        game.resize(1280, 720);
+        game
+    }
+
+    #[test]
+    fn minimizing_keeps_the_views() {
+        for split_screen in SplitScreen::ALL {
+            for scale_mode in ScaleMode::ALL {
+                let mut game = game();
+                game.set_split_screen(split_screen);
+                game.set_scale_mode(scale_mode);
+                let views = game
+                    .views()
+                    .iter()
+                    .map(|view| (view.viewport, view.camera.width))
+                    .collect::<Vec<_>>();
+
+                game.resize(0, 0);
+                game.resize(1280, 0);
+                for _ in 0..10 {
+                    game.tick(Duration::from_millis(16));
+                }
+                for (view, &(viewport, width)) in game.views().iter().zip(&views) {
+                    assert_eq!(view.viewport, viewport);
+                    assert_eq!(view.camera.width, width);
+                    assert!(view.camera.zoom.is_finite());
+                    assert!(view.camera.position.is_finite());
+                }
+
+                // And it picks up where it left off once restored
+                game.resize(640, 360);
+                game.tick(Duration::from_millis(16));
+                assert!(game.views().iter().all(|view| view.camera.zoom.is_finite()));
+            }
+        }
+    }
+
+    #[test]
+    fn screen_positions_map_back_to_the_world() {
+        for split_screen in SplitScreen::ALL {
+            let mut game = game();
+            game.set_split_screen(split_screen);
+            game.set_scale_mode(ScaleMode::Letterbox);
+            // Wider than the virtual size, so views get bars on the sides
+            game.resize(1920, 720);
+            game.tick(Duration::from_millis(16));
+
+            let screen = glam::vec2(1920.0, 720.0);
+            for view in game.views() {
+                let center = view.world_to_screen(view.camera.position, screen);
+                assert!((center - view.viewport.center() * screen).length() < 1e-3);
+                for world in [glam::vec2(0.0, 0.0), glam::vec2(-40.0, 25.0)] {
+                    let screen_pos = view.world_to_screen(world, screen);
+                    let back = view.screen_to_world(screen_pos, screen);
+                    assert!(
+                        (back - world).length() < 1e-3,
+                        "{world} came back as {back}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn missing_clips_are_errors() {
+        let json = r#"{
+            "sheets": [
+                { "name": "sheet", "image": "a.png", "frame_width": 16, "frame_height": 16 }
+            ],
+            "clips": {
+                "player_idle": { "sheet": "sheet", "frames": [{ "index": 0, "duration": 1.0 }] },
+                "player_move": { "sheet": "sheet", "frames": [{ "index": 0, "duration": 1.0 }] }
+            }
+        }"#;
+        let animations = Arc::new(AnimationLibrary::from_json(json).unwrap());
+
+        let error = Game::new(Duration::from_secs(1), animations).err().unwrap();
+        assert!(error.to_string().contains("pickup_spin"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Short lived decorative particles, simulated on the CPU and drawn in one
+//! instanced draw call.
+
+/// Particles alive at once by default
+pub const DEFAULT_BUDGET: usize = 2000;
+
+/// How particles are spawned and how they change over their life.
+#[derive(Debug, Clone)]
+pub struct ParticleConfig {
+    /// Particles per second from an [`Emitter`], bursts ignore it
+    pub rate: f32,
+    /// Seconds, picked between the two
+    pub lifetime: (f32, f32),
+    /// World units per second, picked between the two
+    pub speed: (f32, f32),
+    /// Angle particles head off at, in radians counterclockwise from +x
+    pub direction: f32,
+    /// How far either side of `direction` particles can head off
+    pub spread: f32,
+    /// Fraction of velocity lost per second, roughly
+    pub drag: f32,
+    /// Color at birth and death, blended in between
+    pub color: (glam::Vec4, glam::Vec4),
+    /// Radius at birth and death, blended in between
+    pub size: (f32, f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Particle {
+    pub(crate) position: glam::Vec2,
+    velocity: glam::Vec2,
+    age: f32,
+    lifetime: f32,
+    drag: f32,
+    color: (glam::Vec4, glam::Vec4),
+    size: (f32, f32),
+}
+
+impl Particle {
+    /// From 0 at birth to 1 at death.
+    fn life(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+
+    pub(crate) fn color(&self) -> glam::Vec4 {
+        self.color.0.lerp(self.color.1, self.life())
+    }
+
+    pub(crate) fn size(&self) -> f32 {
+        self.size.0 + (self.size.1 - self.size.0) * self.life()
+    }
+}
+
+/// Spawns particles continuously at its config's rate.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    pub config: ParticleConfig,
+    /// Particles owed from previous updates
+    accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(config: ParticleConfig) -> Self {
+        Self {
+            config,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, particles: &mut ParticleSystem, position: glam::Vec2, dt: f32) {
+        self.accumulator += self.config.rate * dt;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        particles.burst(&self.config, position, count as usize);
+    }
+}
+
+#[derive(Debug)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    /// Most particles alive at once, new ones are dropped past it
+    budget: usize,
+}
+
+impl ParticleSystem {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            particles: Vec::new(),
+            budget,
+        }
+    }
+
+    /// Drops the newest particles if there are already more than `budget`.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.particles.truncate(budget);
+    }
+
+    /// Spawns `count` particles at once, as many as fit in the budget.
+    pub fn burst(&mut self, config: &ParticleConfig, position: glam::Vec2, count: usize) {
+        let count = count.min(self.budget.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let angle = config.direction + (rand::random::<f32>() * 2.0 - 1.0) * config.spread;
+            let speed = random_between(config.speed);
+            self.particles.push(Particle {
+                position,
+                velocity: glam::Vec2::from_angle(angle) * speed,
+                age: 0.0,
+                lifetime: random_between(config.lifetime).max(f32::EPSILON),
+                drag: config.drag,
+                color: config.color,
+                size: config.size,
+            });
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for particle in &mut self.particles {
+            particle.age += dt;
+            particle.velocity *= (-particle.drag * dt).exp();
+            particle.position += particle.velocity * dt;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+}
+
+fn random_between((min, max): (f32, f32)) -> f32 {
+    min + (max - min) * rand::random::<f32>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Particles that sit still for exactly `lifetime` seconds.
+    fn config(rate: f32, lifetime: f32) -> ParticleConfig {
+        ParticleConfig {
+            rate,
+            lifetime: (lifetime, lifetime),
+            speed: (0.0, 0.0),
+            direction: 0.0,
+            spread: 0.0,
+            drag: 0.0,
+            color: (glam::Vec4::ONE, glam::Vec4::ZERO),
+            size: (1.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn bursts_stop_at_the_budget() {
+        let mut particles = ParticleSystem::new(10);
+        particles.burst(&config(0.0, 1.0), glam::Vec2::ZERO, 25);
+        assert_eq!(particles.particles().len(), 10);
+        particles.burst(&config(0.0, 1.0), glam::Vec2::ZERO, 5);
+        assert_eq!(particles.particles().len(), 10);
+    }
+
+    #[test]
+    fn emitters_stop_at_the_budget() {
+        let mut particles = ParticleSystem::new(5);
+        let mut emitter = Emitter::new(config(100.0, 10.0));
+        emitter.update(&mut particles, glam::Vec2::ZERO, 1.0);
+        assert_eq!(particles.particles().len(), 5);
+        emitter.update(&mut particles, glam::Vec2::ZERO, 1.0);
+        assert_eq!(particles.particles().len(), 5);
+    }
+
+    #[test]
+    fn lowering_the_budget_drops_particles() {
+        let mut particles = ParticleSystem::new(10);
+        particles.burst(&config(0.0, 1.0), glam::Vec2::ZERO, 10);
+        particles.set_budget(4);
+        assert_eq!(particles.particles().len(), 4);
+        particles.set_budget(0);
+        assert!(particles.particles().is_empty());
+
+        // Raising it again doesn't bring any back, but makes room
+        particles.set_budget(10);
+        assert!(particles.particles().is_empty());
+        particles.burst(&config(0.0, 1.0), glam::Vec2::ZERO, 3);
+        assert_eq!(particles.particles().len(), 3);
+    }
+
+    #[test]
+    fn particles_die_after_their_lifetime() {
+        let mut particles = ParticleSystem::new(10);
+        particles.burst(&config(0.0, 0.5), glam::Vec2::ZERO, 3);
+        particles.burst(&config(0.0, 2.0), glam::Vec2::ZERO, 2);
+
+        particles.update(0.25);
+        assert_eq!(particles.particles().len(), 5);
+        particles.update(0.5);
+        assert_eq!(particles.particles().len(), 2);
+        assert!(particles.particles().iter().all(|p| p.lifetime == 2.0));
+        particles.update(1.5);
+        assert!(particles.particles().is_empty());
+    }
+
+    #[test]
+    fn emitters_average_their_rate_over_fractional_frames() {
+        let mut particles = ParticleSystem::new(1000);
+        let mut emitter = Emitter::new(config(10.0, 100.0));
+
+        // Half a particle per frame, so one every other frame
+        emitter.update(&mut particles, glam::Vec2::ZERO, 0.05);
+        assert_eq!(particles.particles().len(), 0);
+        assert!((emitter.accumulator - 0.5).abs() < 1e-5);
+        emitter.update(&mut particles, glam::Vec2::ZERO, 0.05);
+        assert_eq!(particles.particles().len(), 1);
+        assert!(emitter.accumulator.abs() < 1e-5);
+
+        // Whatever's owed is carried over, so nothing is lost over time
+        let dt = 1.0 / 60.0;
+        for frame in 1..=600 {
+            emitter.update(&mut particles, glam::Vec2::ZERO, dt);
+            let expected = 1.0 + 10.0 * dt * frame as f32;
+            let emitted = particles.particles().len() as f32 + emitter.accumulator;
+            assert!(
+                (emitted - expected).abs() < 1e-2,
+                "{emitted} after {frame} frames"
+            );
+            assert!((0.0..1.0).contains(&emitter.accumulator));
+        }
+        assert_eq!(particles.particles().len(), 101);
+    }
+}
@@ -0,0 +1,38 @@
+//! Input from keyboards and gamepads after the app has mapped it to actions,
+//! so states don't need to know about winit or gilrs events.
+
+use crate::ui::NavAction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceId {
+    /// Every keyboard, since winit gives window and raw key events different
+    /// device ids
+    Keyboard,
+    Gamepad(gilrs::GamepadId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputAction {
+    /// Horizontal movement from -1 (left) to 1 (right)
+    MoveX(f32),
+    /// Vertical movement from -1 (down) to 1 (up)
+    MoveY(f32),
+    /// A menu button was pressed
+    Nav(NavAction),
+    /// The device was unplugged
+    Disconnected,
+    /// A key was pressed, for rebinding
+    Key(winit::keyboard::KeyCode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceInput {
+    pub device: DeviceId,
+    pub action: InputAction,
+}
+
+impl DeviceInput {
+    pub fn new(device: DeviceId, action: InputAction) -> Self {
+        Self { device, action }
+    }
+}
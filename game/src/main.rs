@@ -4,7 +4,11 @@ use crate::app::App;
 
 mod game;
 mod app;
+mod input;
 mod render;
+mod settings;
+mod state;
+mod ui;
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
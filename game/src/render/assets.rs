@@ -0,0 +1,382 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, OnceLock, Weak},
+};
+
+use anyhow::Context;
+
+use crate::render::resources::Resources;
+
+/// Something that can be created from the bytes of a resource file.
+pub trait Asset: Sized + Send + Sync + 'static {
+    fn load(ctx: &LoadContext<'_>, bytes: Vec<u8>) -> anyhow::Result<Self>;
+}
+
+pub struct LoadContext<'a> {
+    pub path: &'a Path,
+    gpu: Option<&'a Gpu>,
+}
+
+impl LoadContext<'_> {
+    /// The device and queue to create GPU resources with. Managers made
+    /// without a GPU can still load assets that don't need one.
+    pub fn gpu(&self) -> anyhow::Result<(&wgpu::Device, &wgpu::Queue)> {
+        self.gpu
+            .map(|(device, queue)| (device, queue))
+            .with_context(|| format!("{} needs a GPU to load", self.path.display()))
+    }
+}
+
+type Gpu = (wgpu::Device, wgpu::Queue);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed(Arc<str>),
+}
+
+struct Slot<T> {
+    path: PathBuf,
+    value: OnceLock<T>,
+    state: Mutex<LoadState>,
+    ready: Condvar,
+}
+
+impl<T> Slot<T> {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            value: OnceLock::new(),
+            state: Mutex::new(LoadState::Loading),
+            ready: Condvar::new(),
+        }
+    }
+
+    fn finish(&self, result: anyhow::Result<T>) {
+        let state = match result {
+            Ok(value) => {
+                let _ = self.value.set(value);
+                LoadState::Loaded
+            }
+            Err(e) => {
+                log::error!("Failed to load {}: {e:#}", self.path.display());
+                LoadState::Failed(format!("{e:#}").into())
+            }
+        };
+        *self.state.lock().unwrap() = state;
+        self.ready.notify_all();
+    }
+}
+
+/// A shared reference to an asset owned by the [`AssetManager`]. The asset is
+/// freed once the last handle to it is dropped.
+pub struct Handle<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Handle")
+            .field(&self.slot.path)
+            .field(&self.state())
+            .finish()
+    }
+}
+
+impl<T> Handle<T> {
+    pub fn state(&self) -> LoadState {
+        self.slot.state.lock().unwrap().clone()
+    }
+
+    /// Returns the asset if it has finished loading.
+    pub fn get(&self) -> Option<&T> {
+        self.slot.value.get()
+    }
+
+    /// Blocks until the asset has either loaded or failed.
+    pub fn wait(&self) -> anyhow::Result<&T> {
+        let mut state = self.slot.state.lock().unwrap();
+        while *state == LoadState::Loading {
+            state = self.slot.ready.wait(state).unwrap();
+        }
+        match &*state {
+            LoadState::Failed(e) => anyhow::bail!("{}: {e}", self.slot.path.display()),
+            _ => Ok(self.get().unwrap()),
+        }
+    }
+}
+
+trait CacheEntry: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    /// `None` once the asset has been dropped.
+    fn state(&self) -> Option<LoadState>;
+}
+
+impl<T: Send + Sync + 'static> CacheEntry for Weak<Slot<T>> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn state(&self) -> Option<LoadState> {
+        self.upgrade()
+            .map(|slot| slot.state.lock().unwrap().clone())
+    }
+}
+
+type Loader = Arc<dyn Fn(&Path) -> anyhow::Result<Vec<u8>> + Send + Sync>;
+
+/// Loads and caches assets by path. Loading the same path twice hands out
+/// another handle to the same asset for as long as one is still alive.
+pub struct AssetManager {
+    loader: Loader,
+    gpu: Option<Arc<Gpu>>,
+    cache: Mutex<HashMap<(TypeId, PathBuf), Box<dyn CacheEntry>>>,
+}
+
+impl AssetManager {
+    pub fn new(
+        resources: impl Resources + Send + Sync + 'static,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        Self {
+            gpu: Some(Arc::new((device.clone(), queue.clone()))),
+            ..Self::without_gpu(resources)
+        }
+    }
+
+    /// A manager that can only load assets that stay on the CPU.
+    pub fn without_gpu(resources: impl Resources + Send + Sync + 'static) -> Self {
+        Self {
+            loader: Arc::new(move |path| pollster::block_on(resources.load_binary_async(path))),
+            gpu: None,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached slot for `path`, or a new one if the asset isn't
+    /// loaded or failed to load. The bool is true when the caller is
+    /// responsible for loading it.
+    fn slot<T: Asset>(&self, path: &Path) -> (Arc<Slot<T>>, bool) {
+        let mut cache = self.cache.lock().unwrap();
+        let key = (TypeId::of::<T>(), path.to_owned());
+        if let Some(slot) = cache
+            .get(&key)
+            .and_then(|entry| entry.as_any().downcast_ref::<Weak<Slot<T>>>())
+            .and_then(Weak::upgrade)
+            && !matches!(*slot.state.lock().unwrap(), LoadState::Failed(_))
+        {
+            return (slot, false);
+        }
+        let slot = Arc::new(Slot::new(path.to_owned()));
+        cache.insert(key, Box::new(Arc::downgrade(&slot)));
+        (slot, true)
+    }
+
+    /// Starts loading `path` on a background thread. Use [`Handle::get`] or
+    /// [`Handle::state`] to find out when it's ready.
+    pub fn load<T: Asset>(&self, path: impl AsRef<Path>) -> Handle<T> {
+        let (slot, needs_load) = self.slot::<T>(path.as_ref());
+        if needs_load {
+            let slot = slot.clone();
+            let loader = self.loader.clone();
+            let gpu = self.gpu.clone();
+            std::thread::spawn(move || {
+                let result = load_asset(&slot.path, &loader, gpu.as_deref());
+                slot.finish(result);
+            });
+        }
+        Handle { slot }
+    }
+
+    /// Loads `path` on the current thread, waiting on any load of the same
+    /// asset already in progress.
+    pub fn load_blocking<T: Asset>(&self, path: impl AsRef<Path>) -> anyhow::Result<Handle<T>> {
+        let (slot, needs_load) = self.slot::<T>(path.as_ref());
+        if needs_load {
+            let result = load_asset(&slot.path, &self.loader, self.gpu.as_deref());
+            slot.finish(result);
+        }
+        let handle = Handle { slot };
+        handle.wait()?;
+        Ok(handle)
+    }
+
+    /// Paths of the assets that are still loading.
+    pub fn pending(&self) -> Vec<PathBuf> {
+        self.cache
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.state() == Some(LoadState::Loading))
+            .map(|((_, path), _)| path.clone())
+            .collect()
+    }
+
+    /// Forgets cache entries whose assets have been dropped.
+    pub fn collect_garbage(&self) {
+        self.cache
+            .lock()
+            .unwrap()
+            .retain(|_, entry| entry.state().is_some());
+    }
+}
+
+fn load_asset<T: Asset>(path: &Path, loader: &Loader, gpu: Option<&Gpu>) -> anyhow::Result<T> {
+    let bytes = loader(path)?;
+    T::load(&LoadContext { path, gpu }, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::resources::InMemoryResources;
+
+    #[derive(Debug)]
+    struct Text(String);
+
+    impl Asset for Text {
+        fn load(_ctx: &LoadContext<'_>, bytes: Vec<u8>) -> anyhow::Result<Self> {
+            Ok(Self(String::from_utf8(bytes)?))
+        }
+    }
+
+    fn manager(resources: InMemoryResources) -> AssetManager {
+        AssetManager::without_gpu(resources)
+    }
+
+    /// Blocks loading until the returned sender is dropped.
+    fn gated(resources: InMemoryResources) -> (AssetManager, std::sync::mpsc::Sender<()>) {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let receiver = Mutex::new(receiver);
+        let loader: Loader = Arc::new(move |path| {
+            let _ = receiver.lock().unwrap().recv();
+            resources.load_binary(path)
+        });
+        let assets = AssetManager {
+            loader,
+            gpu: None,
+            cache: Mutex::new(HashMap::new()),
+        };
+        (assets, sender)
+    }
+
+    #[test]
+    fn loading_a_path_twice_shares_the_asset() {
+        let assets = manager(InMemoryResources::new().with("a.txt", "a"));
+
+        let first = assets.load_blocking::<Text>("a.txt").unwrap();
+        let second = assets.load_blocking::<Text>("a.txt").unwrap();
+        assert!(Arc::ptr_eq(&first.slot, &second.slot));
+        assert_eq!(first.get().unwrap().0, "a");
+        assert_eq!(assets.cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn types_are_cached_separately() {
+        let assets = manager(InMemoryResources::new().with("a.txt", "a"));
+
+        let _text = assets.load_blocking::<Text>("a.txt").unwrap();
+        assert!(assets.load_blocking::<image::RgbaImage>("a.txt").is_err());
+        assert_eq!(assets.cache.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn collect_garbage_forgets_dropped_assets() {
+        let resources = InMemoryResources::new().with("a.txt", "old");
+        let assets = manager(resources.clone());
+
+        let handle = assets.load_blocking::<Text>("a.txt").unwrap();
+        let clone = handle.clone();
+        drop(handle);
+        assets.collect_garbage();
+        assert_eq!(assets.cache.lock().unwrap().len(), 1);
+
+        drop(clone);
+        assets.collect_garbage();
+        assert!(assets.cache.lock().unwrap().is_empty());
+
+        // Nothing holds the old asset, so it's loaded again
+        resources.insert("a.txt", "new");
+        let handle = assets.load_blocking::<Text>("a.txt").unwrap();
+        assert_eq!(handle.get().unwrap().0, "new");
+    }
+
+    #[test]
+    fn wait_blocks_until_a_background_load_finishes() {
+        let assets = manager(InMemoryResources::new().with("a.txt", "a"));
+
+        let handle = assets.load::<Text>("a.txt");
+        assert_eq!(handle.wait().unwrap().0, "a");
+        assert_eq!(handle.state(), LoadState::Loaded);
+    }
+
+    #[test]
+    fn missing_files_fail_to_load() {
+        let assets = manager(InMemoryResources::new());
+
+        let handle = assets.load::<Text>("missing.txt");
+        let error = handle.wait().unwrap_err();
+        assert!(error.to_string().contains("missing.txt"));
+        assert!(matches!(handle.state(), LoadState::Failed(_)));
+        assert!(handle.get().is_none());
+        assert!(assets.load_blocking::<Text>("missing.txt").is_err());
+    }
+
+    #[test]
+    fn parse_errors_fail_to_load() {
+        let assets = manager(InMemoryResources::new().with("bad.txt", [0xffu8]));
+
+        let handle = assets.load::<Text>("bad.txt");
+        assert!(handle.wait().is_err());
+        assert!(matches!(handle.state(), LoadState::Failed(_)));
+    }
+
+    #[test]
+    fn failed_loads_are_retried() {
+        let resources = InMemoryResources::new();
+        let assets = manager(resources.clone());
+
+        let failed = assets.load::<Text>("late.txt");
+        assert!(failed.wait().is_err());
+
+        resources.insert("late.txt", "added after failing");
+        let handle = assets.load_blocking::<Text>("late.txt").unwrap();
+        assert_eq!(handle.get().unwrap().0, "added after failing");
+        // Handles to the failed load keep their state
+        assert!(matches!(failed.state(), LoadState::Failed(_)));
+    }
+
+    #[test]
+    fn pending_lists_loads_in_progress() {
+        let (assets, gate) = gated(InMemoryResources::new().with("a.txt", "a"));
+
+        let handle = assets.load::<Text>("a.txt");
+        assert_eq!(assets.pending(), [PathBuf::from("a.txt")]);
+
+        drop(gate);
+        handle.wait().unwrap();
+        assert!(assets.pending().is_empty());
+    }
+
+    #[test]
+    fn gpu_assets_fail_without_a_gpu() {
+        let assets = manager(InMemoryResources::new().with("a.png", [0u8]));
+
+        let error = assets
+            .load_blocking::<crate::render::texture::Texture>("a.png")
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("needs a GPU"));
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use crate::render::texture::Texture;
+
+/// Where an image ended up in a [`TextureAtlas`].
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasRegion {
+    /// Min uv in `xy`, max uv in `zw`.
+    pub uv_rect: glam::Vec4,
+    /// Size of the source image in pixels.
+    #[cfg(test)]
+    pub size: glam::UVec2,
+}
+
+/// Packs images into a single texture so they can share one bind group and be
+/// drawn in one instanced draw call.
+pub struct AtlasBuilder {
+    images: Vec<(String, image::RgbaImage)>,
+    padding: u32,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            images: Vec::new(),
+            padding: 1,
+        }
+    }
+
+    /// Pixels around each image. The edges of the image are extruded into the
+    /// padding so linear filtering doesn't bleed neighbouring images in.
+    #[cfg(test)]
+    pub fn padding(mut self, value: u32) -> Self {
+        self.padding = value;
+        self
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, image: image::RgbaImage) -> &mut Self {
+        self.images.push((name.into(), image));
+        self
+    }
+
+    /// Splits a sprite sheet into frames named `{name}/{index}`, numbered left
+    /// to right then top to bottom.
+    pub fn add_sheet(
+        &mut self,
+        name: &str,
+        image: &image::RgbaImage,
+        frame_width: u32,
+        frame_height: u32,
+    ) -> &mut Self {
+        let columns = image.width() / frame_width;
+        let rows = image.height() / frame_height;
+        for row in 0..rows {
+            for column in 0..columns {
+                let frame = image::imageops::crop_imm(
+                    image,
+                    column * frame_width,
+                    row * frame_height,
+                    frame_width,
+                    frame_height,
+                )
+                .to_image();
+                self.add(format!("{name}/{}", row * columns + column), frame);
+            }
+        }
+        self
+    }
+
+    /// Packs the images using a simple shelf packer, returning the atlas image
+    /// and each image's region in it.
+    pub fn pack(
+        &self,
+        max_size: u32,
+    ) -> anyhow::Result<(image::RgbaImage, HashMap<String, AtlasRegion>)> {
+        let pad = self.padding;
+
+        // Tallest first keeps shelves tight
+        let mut order = (0..self.images.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].1.height()));
+
+        let area = self
+            .images
+            .iter()
+            .map(|(_, img)| (img.width() + pad * 2) as u64 * (img.height() + pad * 2) as u64)
+            .sum::<u64>();
+        let widest = self
+            .images
+            .iter()
+            .map(|(_, img)| img.width() + pad * 2)
+            .max()
+            .unwrap_or(1);
+
+        let mut width = widest
+            .max((area as f64).sqrt().ceil() as u32)
+            .next_power_of_two();
+        let (width, height, positions) = loop {
+            if width > max_size {
+                anyhow::bail!("Images don't fit in a {max_size}x{max_size} atlas");
+            }
+            let (height, positions) = self.shelf_pack(&order, width);
+            if height <= max_size {
+                break (width, height.max(1), positions);
+            }
+            width *= 2;
+        };
+
+        let mut atlas = image::RgbaImage::new(width, height);
+        let mut regions = HashMap::new();
+        let inv_size = glam::vec2(1.0 / width as f32, 1.0 / height as f32);
+
+        for (i, glam::UVec2 { x, y }) in positions {
+            let (name, img) = &self.images[i];
+            let (w, h) = img.dimensions();
+
+            // Copy the image, clamping to its edge to fill the padding
+            if w > 0 && h > 0 {
+                for py in 0..h + pad * 2 {
+                    for px in 0..w + pad * 2 {
+                        let sx = px.saturating_sub(pad).min(w - 1);
+                        let sy = py.saturating_sub(pad).min(h - 1);
+                        atlas.put_pixel(x + px, y + py, *img.get_pixel(sx, sy));
+                    }
+                }
+            }
+
+            let min = glam::vec2((x + pad) as f32, (y + pad) as f32) * inv_size;
+            let max = glam::vec2((x + pad + w) as f32, (y + pad + h) as f32) * inv_size;
+            regions.insert(
+                name.clone(),
+                AtlasRegion {
+                    uv_rect: glam::vec4(min.x, min.y, max.x, max.y),
+                    #[cfg(test)]
+                    size: glam::uvec2(w, h),
+                },
+            );
+        }
+
+        Ok((atlas, regions))
+    }
+
+    /// Returns the atlas height and the top left corner of each image.
+    fn shelf_pack(&self, order: &[usize], width: u32) -> (u32, Vec<(usize, glam::UVec2)>) {
+        let pad = self.padding;
+        let mut positions = Vec::with_capacity(order.len());
+        let mut x = 0;
+        let mut y = 0;
+        let mut shelf_height = 0;
+        for &i in order {
+            let img = &self.images[i].1;
+            let w = img.width() + pad * 2;
+            let h = img.height() + pad * 2;
+            if x + w > width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            positions.push((i, glam::uvec2(x, y)));
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+        (y + shelf_height, positions)
+    }
+
+    pub fn build(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+    ) -> anyhow::Result<TextureAtlas> {
+        let max_size = device.limits().max_texture_dimension_2d;
+        let (image, regions) = self
+            .pack(max_size)
+            .context("Unable to pack texture atlas")?;
+        Ok(TextureAtlas {
+            texture: Texture::from_image(device, queue, &image, label),
+            regions,
+        })
+    }
+}
+
+pub struct TextureAtlas {
+    texture: Texture,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> image::RgbaImage {
+        image::RgbaImage::from_pixel(width, height, image::Rgba([value, value, value, 255]))
+    }
+
+    /// Pixel rects of each region, including their padding.
+    fn padded_rects(
+        atlas: &image::RgbaImage,
+        regions: &HashMap<String, AtlasRegion>,
+        pad: u32,
+    ) -> Vec<(glam::UVec2, glam::UVec2)> {
+        let size = glam::vec2(atlas.width() as f32, atlas.height() as f32);
+        regions
+            .values()
+            .map(|region| {
+                let min = (region.uv_rect.truncate().truncate() * size)
+                    .round()
+                    .as_uvec2();
+                (min - pad, min + region.size + pad)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn images_dont_overlap() {
+        let mut builder = AtlasBuilder::new();
+        for i in 0..40 {
+            builder.add(format!("{i}"), solid(5 + i * 7 % 23, 3 + i * 11 % 17, 0));
+        }
+        let (atlas, regions) = builder.pack(1024).unwrap();
+        assert_eq!(regions.len(), 40);
+
+        let rects = padded_rects(&atlas, &regions, 1);
+        for (i, &(min_a, max_a)) in rects.iter().enumerate() {
+            assert!(max_a.x <= atlas.width() && max_a.y <= atlas.height());
+            for &(min_b, max_b) in &rects[i + 1..] {
+                let overlaps = min_a.x < max_b.x
+                    && min_b.x < max_a.x
+                    && min_a.y < max_b.y
+                    && min_b.y < max_a.y;
+                assert!(!overlaps, "{min_a}..{max_a} overlaps {min_b}..{max_b}");
+            }
+        }
+    }
+
+    #[test]
+    fn atlas_grows_to_fit() {
+        let mut builder = AtlasBuilder::new();
+        builder.add("wide", solid(100, 10, 0));
+        let (atlas, _) = builder.pack(1024).unwrap();
+        assert_eq!(atlas.width(), 128);
+
+        for i in 0..16 {
+            builder.add(format!("{i}"), solid(62, 62, 0));
+        }
+        let (atlas, regions) = builder.pack(1024).unwrap();
+        assert!(atlas.width().is_power_of_two());
+        assert!(atlas.width() * atlas.height() >= 17 * 64 * 64);
+        assert_eq!(regions.len(), 17);
+    }
+
+    #[test]
+    fn too_many_images_fail_to_pack() {
+        let mut builder = AtlasBuilder::new();
+        for i in 0..3 {
+            builder.add(format!("{i}"), solid(40, 40, 0));
+        }
+        assert!(builder.pack(64).is_err());
+        assert!(builder.pack(128).is_ok());
+    }
+
+    #[test]
+    fn padding_repeats_the_edge() {
+        let mut image = solid(2, 2, 0);
+        image.put_pixel(0, 0, image::Rgba([10, 20, 30, 255]));
+        image.put_pixel(1, 1, image::Rgba([40, 50, 60, 255]));
+        let mut builder = AtlasBuilder::new().padding(2);
+        builder.add("image", image.clone());
+        let (atlas, regions) = builder.pack(64).unwrap();
+
+        let (min, max) = padded_rects(&atlas, &regions, 2)[0];
+        assert_eq!(max - min, glam::uvec2(6, 6));
+        // Corners of the padding match the image's corners
+        assert_eq!(atlas.get_pixel(min.x, min.y), image.get_pixel(0, 0));
+        assert_eq!(atlas.get_pixel(max.x - 1, max.y - 1), image.get_pixel(1, 1));
+        assert_eq!(atlas.get_pixel(min.x + 2, min.y + 2), image.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn sheets_split_into_numbered_frames() {
+        let mut sheet = solid(30, 20, 0);
+        sheet.put_pixel(25, 15, image::Rgba([255, 0, 0, 255]));
+        let mut builder = AtlasBuilder::new();
+        builder.add_sheet("sheet", &sheet, 10, 10);
+        let (atlas, regions) = builder.pack(256).unwrap();
+
+        assert_eq!(regions.len(), 6);
+        let last = regions["sheet/5"];
+        assert_eq!(last.size, glam::uvec2(10, 10));
+        let size = glam::vec2(atlas.width() as f32, atlas.height() as f32);
+        let min = (last.uv_rect.truncate().truncate() * size)
+            .round()
+            .as_uvec2();
+        assert_eq!(
+            atlas.get_pixel(min.x + 5, min.y + 5),
+            &image::Rgba([255, 0, 0, 255])
+        );
+    }
+}
@@ -53,7 +53,6 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable> BackedBuffer<T> {
         Batch::new(self, device, queue)
     }
 
-    #[allow(unused)]
     pub fn batch_indexed<'a>(
         &'a mut self,
         device: &'a wgpu::Device,
@@ -63,7 +62,6 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable> BackedBuffer<T> {
         IndexedBatch::new(device, queue, self, indices)
     }
 
-    #[allow(unused)]
     pub fn slice(&self) -> wgpu::BufferSlice<'_> {
         self.buffer.slice(..)
     }
@@ -158,14 +156,12 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> IndexedBatch<'a, T> {
         }
     }
 
-    #[allow(unused)]
     pub fn vertex(&mut self, v: T) -> &mut Self {
         self.indices.data.push(self.batch.vertices.len());
         self.batch.push(v);
         self
     }
 
-    #[allow(unused)]
     pub fn line(&mut self, a: T, b: T) -> &mut Self {
         self.vertex(a);
         self.vertex(b);
@@ -176,7 +172,7 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> IndexedBatch<'a, T> {
 impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for IndexedBatch<'a, T> {
     fn drop(&mut self) {
         if self.start_index < self.indices.data.len() {
-            let size = (self.indices.data.capacity() * size_of::<T>()) as wgpu::BufferAddress;
+            let size = (self.indices.data.capacity() * size_of::<u32>()) as wgpu::BufferAddress;
             if size > self.indices.buffer.size() {
                 self.indices.buffer = self.batch.device.create_buffer(&wgpu::BufferDescriptor {
                     label: None,
@@ -191,7 +187,7 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for IndexedBatch<'a, T> {
                 );
                 self.indices.version += 1;
             } else {
-                let offset = (self.start_index * size_of::<T>()) as wgpu::BufferAddress;
+                let offset = (self.start_index * size_of::<u32>()) as wgpu::BufferAddress;
                 self.batch.queue.write_buffer(
                     &self.indices.buffer,
                     offset,
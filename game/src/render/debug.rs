@@ -0,0 +1,70 @@
+use crate::render::{
+    bindings::{CameraBinder, CameraBinding},
+    buffer::BackedBuffer,
+    utils::RenderPipelineBuilder,
+    vertex::LineVertex,
+};
+
+/// Draws one pixel wide lines for [`crate::game::debug::DebugDraw`].
+pub struct DebugPipeline {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_binder: &CameraBinder,
+        sample_count: u32,
+    ) -> anyhow::Result<Self> {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[camera_binder.layout()],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("debug.wgsl"));
+        let pipeline = RenderPipelineBuilder::new()
+            .label("DebugPipeline")
+            .layout(&layout)
+            .vertex(wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[LineVertex::VERTEX_LAYOUT],
+            })
+            .fragment(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .multisample(sample_count)
+            .build(device)?;
+
+        Ok(Self { pipeline })
+    }
+
+    /// Draws pairs of indices in `indices` as lines.
+    pub fn draw<'a, 'b: 'a>(
+        &'a self,
+        pass: &'a mut wgpu::RenderPass<'b>,
+        camera: &'a CameraBinding,
+        vertices: &'a BackedBuffer<LineVertex>,
+        indices: &'a BackedBuffer<u32>,
+    ) {
+        if indices.len() == 0 {
+            return;
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, camera.bind_group(), &[]);
+        pass.set_index_buffer(indices.slice(), wgpu::IndexFormat::Uint32);
+        pass.set_vertex_buffer(0, vertices.slice());
+        pass.draw_indexed(0..indices.len(), 0, 0..1);
+    }
+}
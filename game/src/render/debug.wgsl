@@ -0,0 +1,31 @@
+struct LineVertex {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    color: vec4<f32>,
+}
+
+struct VsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    color: vec4<f32>,
+}
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(vertex: LineVertex) -> VsOut {
+    return VsOut(camera.view_proj * vec4(vertex.position, 0.0, 1.0), vertex.color);
+}
+
+@fragment
+fn fs_main(vs: VsOut) -> @location(0) vec4<f32> {
+    return vs.color;
+}
@@ -1,112 +1,265 @@
 use std::{
     collections::HashMap,
     io::{Cursor, Read},
-    path::Path,
 };
 
+use anyhow::Context;
+
 use glam::{Vec2, vec2};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
 use crate::render::{
-    bindings::{self, CameraBinder, CameraBinding},
-    resources::Resources,
+    assets::{Asset, Handle, LoadContext},
+    bindings::{CameraBinder, CameraBinding},
+    texture::Texture,
     utils::RenderPipelineBuilder,
 };
 
-use super::vertex::Vertex2d;
+use super::{
+    rich_text::TextSpan,
+    text_layout::{TextLayout, layout_text},
+    utils::Rect,
+    vertex::TextVertex,
+};
 
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 struct FontUniforms {
     unit_range: Vec2,
+    _padding: [u32; 2],
+}
+
+/// How a run of text is drawn. Widths and offsets are in the units of the
+/// camera the text is drawn with, so pixels for UI text.
+///
+/// Outlines and glows can only extend as far as the font's distance field,
+/// which is `distanceRange / 2` atlas pixels scaled to the drawn size. Fonts
+/// that need wide effects should be generated with a larger range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub outline_width: f32,
+    pub outline_color: glam::Vec4,
+    /// The shadow is a copy of the text, including its outline, drawn at
+    /// this offset underneath it.
+    pub shadow_offset: glam::Vec2,
+    /// A transparent shadow isn't drawn at all
+    pub shadow_color: glam::Vec4,
+    pub shadow_softness: f32,
+    /// Distance the glow fades out over
+    pub glow_width: f32,
+    pub glow_color: glam::Vec4,
+    /// Grows the glyphs, in distance field units
+    pub in_bias: f32,
+    /// Shifts the antialiased edge outwards
+    pub out_bias: f32,
+    /// 0 for a linear edge, 1 for a smoothstep
+    pub smoothness: f32,
+    /// How much of a 4 tap supersample to mix in, which helps small text
+    pub super_sample: f32,
+    pub inv_gamma: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            outline_width: 0.0,
+            outline_color: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
+            shadow_offset: glam::Vec2::ZERO,
+            shadow_color: glam::Vec4::ZERO,
+            shadow_softness: 0.0,
+            glow_width: 0.0,
+            glow_color: glam::Vec4::ONE,
+            in_bias: 0.0,
+            out_bias: 0.0,
+            smoothness: 0.0,
+            super_sample: 0.0,
+            inv_gamma: 1.0,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn with_outline(mut self, width: f32, color: glam::Vec4) -> Self {
+        self.outline_width = width;
+        self.outline_color = color;
+        self
+    }
+
+    pub fn with_shadow(mut self, offset: glam::Vec2, color: glam::Vec4) -> Self {
+        self.shadow_offset = offset;
+        self.shadow_color = color;
+        self
+    }
+
+    fn has_shadow(&self) -> bool {
+        self.shadow_color.w > 0.0
+    }
+
+    fn uniforms(&self) -> TextStyleUniforms {
+        TextStyleUniforms {
+            outline_color: self.outline_color,
+            shadow_color: self.shadow_color,
+            glow_color: self.glow_color,
+            shadow_offset: self.shadow_offset,
+            outline_width: self.outline_width,
+            shadow_softness: self.shadow_softness,
+            glow_width: self.glow_width,
+            in_bias: self.in_bias,
+            out_bias: self.out_bias,
+            smoothness: self.smoothness,
+            super_sample: self.super_sample,
+            inv_gamma: self.inv_gamma,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TextStyleUniforms {
+    outline_color: glam::Vec4,
+    shadow_color: glam::Vec4,
+    glow_color: glam::Vec4,
+    shadow_offset: Vec2,
+    outline_width: f32,
+    shadow_softness: f32,
+    glow_width: f32,
     in_bias: f32,
     out_bias: f32,
     smoothness: f32,
     super_sample: f32,
     inv_gamma: f32,
-    _padding: u32,
+    _padding: [f32; 2],
 }
 
 pub struct TextPipeline {
-    font_uniforms: FontUniforms,
-    font_uniform_buffer: wgpu::Buffer,
     text_pipeline: wgpu::RenderPipeline,
-    font_uniform_bg: wgpu::BindGroup,
-    font_atlas: wgpu::BindGroup,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    surface_format: wgpu::TextureFormat,
+    font_layout: wgpu::BindGroupLayout,
+    style_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
 }
 
 impl TextPipeline {
     pub fn new(
         device: &wgpu::Device,
-        font: &Font,
         surface_format: wgpu::TextureFormat,
         camera_binder: &CameraBinder,
-        texture_binder: &bindings::TextureBinder,
     ) -> anyhow::Result<Self> {
         let shader = device.create_shader_module(wgpu::include_wgsl!("font.wgsl"));
-        let font_uniforms = FontUniforms {
-            unit_range: vec2(
-                font.info.distance_field.distance_range as f32 / font.info.common.scale_w as f32,
-                font.info.distance_field.distance_range as f32 / font.info.common.scale_h as f32,
-            ),
-            in_bias: 0.0,
-            out_bias: 0.0,
-            smoothness: 0.0,
-            super_sample: 0.0,
-            inv_gamma: 1.0 / 1.0,
-            _padding: 0,
-        };
 
-        let font_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("font_uniform_buffer"),
-            contents: bytemuck::bytes_of(&font_uniforms),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-        });
-
-        let font_uniform_bg_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("font_uniform_bg_layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
+        let font_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("font_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
                     count: None,
-                }],
-            });
+                },
+            ],
+        });
 
-        let font_uniform_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("font_uniform_bg"),
-            layout: &font_uniform_bg_layout,
-            entries: &[wgpu::BindGroupEntry {
+        let style_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("text_style_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                resource: font_uniform_buffer.as_entire_binding(),
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             }],
         });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("pipeline_layout"),
-            bind_group_layouts: &[
-                texture_binder.layout(),
-                camera_binder.layout(),
-                &font_uniform_bg_layout,
-            ],
+            bind_group_layouts: &[&font_layout, camera_binder.layout(), &style_layout],
             push_constant_ranges: &[],
         });
 
-        let text_pipeline = RenderPipelineBuilder::new()
-            .layout(&pipeline_layout)
+        let text_pipeline =
+            Self::build_pipeline(device, &shader, &pipeline_layout, surface_format, 1)?;
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            text_pipeline,
+            shader,
+            pipeline_layout,
+            surface_format,
+            font_layout,
+            style_layout,
+            sampler,
+        })
+    }
+
+    /// Builds the pipeline for passes with `sample_count` samples, to swap
+    /// in with [`TextPipeline::set_pipeline`]. Only the pipeline changes, so
+    /// fonts registered already stay usable.
+    pub fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        sample_count: u32,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        Self::build_pipeline(
+            device,
+            &self.shader,
+            &self.pipeline_layout,
+            self.surface_format,
+            sample_count,
+        )
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.text_pipeline = pipeline;
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        layout: &wgpu::PipelineLayout,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        RenderPipelineBuilder::new()
+            .layout(layout)
             .vertex(wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("textured"),
                 compilation_options: Default::default(),
-                buffers: &[Vertex2d::VERTEX_LAYOUT],
+                buffers: &[TextVertex::VERTEX_LAYOUT],
             })
             .fragment(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("msdf_text"),
                 compilation_options: Default::default(),
                 targets: &[Some(wgpu::ColorTargetState {
@@ -116,135 +269,98 @@ impl TextPipeline {
                 })],
             })
             .cull_mode(None)
-            .build(&device)?;
+            .multisample(sample_count)
+            .build(device)
+    }
+
+    /// Creates the bind group holding a font's pages and its uniforms.
+    fn bind_font(&self, device: &wgpu::Device, font: &Font) -> wgpu::BindGroup {
+        let font_uniforms = FontUniforms {
+            unit_range: vec2(
+                font.info.distance_field.distance_range as f32 / font.info.common.scale_w as f32,
+                font.info.distance_field.distance_range as f32 / font.info.common.scale_h as f32,
+            ),
+            _padding: [0; 2],
+        };
+
+        let font_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("font_uniform_buffer"),
+            contents: bytemuck::bytes_of(&font_uniforms),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
 
-        let font_atlas = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("font_atlas"),
-            layout: &text_pipeline.get_bind_group_layout(0),
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("font_bind_group"),
+            layout: &self.font_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(
-                        &font.texture.create_view(&Default::default()),
-                    ),
+                    resource: wgpu::BindingResource::TextureView(font.pages.view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&device.create_sampler(
-                        &wgpu::SamplerDescriptor {
-                            min_filter: wgpu::FilterMode::Linear,
-                            mag_filter: wgpu::FilterMode::Linear,
-                            ..Default::default()
-                        },
-                    )),
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: font_uniform_buffer.as_entire_binding(),
                 },
             ],
-        });
-
-        Ok(Self {
-            font_uniforms,
-            font_uniform_buffer,
-            font_uniform_bg,
-            text_pipeline,
-            font_atlas,
         })
     }
 
-    pub fn buffer_text(
-        &self,
-        font: &Font,
-        device: &wgpu::Device,
-        text: &str,
-    ) -> anyhow::Result<TextBuffer> {
-        let (verts, indices) = generate_text_data(font, text);
-
-        let vb = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some(text),
-            contents: bytemuck::cast_slice(&verts),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
+    pub(super) fn bind_style(&self, device: &wgpu::Device, style: TextStyle) -> TextStyleBinding {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("text_style"),
+            contents: bytemuck::bytes_of(&style.uniforms()),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
         });
-        let ib = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some(text),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDEX,
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("text_style_bind_group"),
+            layout: &self.style_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
         });
-
-        Ok(TextBuffer {
-            num_indices: indices.len() as _,
-            indices: ib,
-            vertices: vb,
-        })
-    }
-
-    pub fn update_text(
-        &self,
-        font: &Font,
-        text: &str,
-        buffer: &mut TextBuffer,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) {
-        let (verts, indices) = generate_text_data(font, text);
-        if verts.len() * size_of::<Vertex2d>() > buffer.vertices.size() as usize {
-            buffer.vertices = device.create_buffer_init(&BufferInitDescriptor {
-                label: Some(text),
-                contents: bytemuck::cast_slice(&verts),
-                usage: buffer.vertices.usage(),
-            });
-        } else {
-            queue.write_buffer(&buffer.vertices, 0, bytemuck::cast_slice(&verts));
-        }
-        if indices.len() * size_of::<Vertex2d>() > buffer.indices.size() as usize {
-            buffer.indices = device.create_buffer_init(&BufferInitDescriptor {
-                label: Some(text),
-                contents: bytemuck::cast_slice(&indices),
-                usage: buffer.indices.usage(),
-            });
-        } else {
-            queue.write_buffer(&buffer.indices, 0, bytemuck::cast_slice(&indices));
-        }
-        buffer.num_indices = indices.len() as _;
+        TextStyleBinding { style, bind_group }
     }
 
-    pub fn draw_text(
+    /// Sets the pipeline and everything but the style and geometry.
+    pub(super) fn bind(
         &self,
         pass: &mut wgpu::RenderPass<'_>,
-        text: &TextBuffer,
+        fonts: &FontRegistry,
+        font: FontId,
         camera_binding: &CameraBinding,
     ) {
-        pass.set_bind_group(0, &self.font_atlas, &[]);
-        pass.set_bind_group(1, camera_binding.bind_group(), &[]);
-        pass.set_bind_group(2, &self.font_uniform_bg, &[]);
-        pass.set_vertex_buffer(0, text.vertices.slice(..));
-        pass.set_index_buffer(text.indices.slice(..), wgpu::IndexFormat::Uint32);
         pass.set_pipeline(&self.text_pipeline);
-        pass.draw_indexed(0..text.num_indices as u32, 0, 0..1);
+        pass.set_bind_group(0, fonts.bind_group(font), &[]);
+        pass.set_bind_group(1, camera_binding.bind_group(), &[]);
     }
 }
 
-fn generate_text_data(font: &Font, text: &str) -> (Vec<Vertex2d>, Vec<u32>) {
-    let tex_width = font.texture.width() as f32;
-    let tex_height = font.texture.height() as f32;
+pub(super) fn generate_text_data(
+    font: &Font,
+    text: &[TextSpan],
+    layout: &TextLayout,
+) -> (Vec<TextVertex>, Vec<u32>, Rect) {
+    let tex_width = font.pages.width() as f32;
+    let tex_height = font.pages.height() as f32;
 
-    let mut cursor_x = 0.0;
-    let mut cursor_y = 0.0;
-    let mut i = 0u32;
-
-    let mut verts = Vec::new();
-    let mut indices = Vec::new();
-    for c in text.chars() {
-        if c == '\n' {
-            cursor_x = 0.0;
-            cursor_y += font.info.common.line_height as f32;
-            continue;
-        }
+    let laid_out = layout_text(font, text, layout);
 
-        let glyph = font.glyph(c).unwrap_or_else(|| font.unknown_glyph());
+    let mut verts = Vec::with_capacity(laid_out.glyphs.len() * 4);
+    let mut indices = Vec::with_capacity(laid_out.glyphs.len() * 6);
+    let mut i = 0u32;
 
-        if glyph.width == 0 || glyph.height == 0 {
-            cursor_x += glyph.xadvance as f32;
-            continue;
-        }
+    for placed in &laid_out.glyphs {
+        let glyph = &font.info.glyphs[placed.glyph];
+        let style = &text[placed.span].style;
+        let color = style.color.into();
+        let page = glyph.page;
+        // Faux italic, shear the top of the glyph to the right
+        let slant = if style.italic { 0.2 } else { 0.0 };
 
         let min_uv = glam::vec2(glyph.x as f32 / tex_width, glyph.y as f32 / tex_height);
         let max_uv = min_uv
@@ -253,115 +369,192 @@ fn generate_text_data(font: &Font, text: &str) -> (Vec<Vertex2d>, Vec<u32>) {
                 glyph.height as f32 / tex_height,
             );
 
-        let p1 = glam::vec2(
-            cursor_x + glyph.xoffset as f32 + 20.0,
-            cursor_y + glyph.yoffset as f32 + 20.0,
-        );
-        let p2 = p1 + glam::vec2(glyph.width as f32, glyph.height as f32);
+        let p1 = placed.rect.min;
+        let p2 = placed.rect.max;
+
+        // y points down, so the top of the glyph is p1.y
+        let top_shift = slant * (p2.y - p1.y);
 
         verts.extend_from_slice(&[
-            Vertex2d {
-                position: glam::vec2(p1.x, p1.y),
+            TextVertex {
+                position: glam::vec2(p1.x + top_shift, p1.y),
                 uv: glam::vec2(min_uv.x, min_uv.y),
+                color,
+                page,
             },
-            Vertex2d {
-                position: glam::vec2(p2.x, p1.y),
+            TextVertex {
+                position: glam::vec2(p2.x + top_shift, p1.y),
                 uv: glam::vec2(max_uv.x, min_uv.y),
+                color,
+                page,
             },
-            Vertex2d {
+            TextVertex {
                 position: glam::vec2(p2.x, p2.y),
                 uv: glam::vec2(max_uv.x, max_uv.y),
+                color,
+                page,
             },
-            Vertex2d {
+            TextVertex {
                 position: glam::vec2(p1.x, p2.y),
                 uv: glam::vec2(min_uv.x, max_uv.y),
+                color,
+                page,
             },
         ]);
 
         indices.extend_from_slice(&[i, i + 1, i + 2, i, i + 2, i + 3]);
-
-        cursor_x += glyph.xadvance as f32;
         i += 4;
     }
-    (verts, indices)
+    (verts, indices, laid_out.bounds)
+}
+
+/// A [`TextStyle`] and the uniforms it's drawn with.
+pub(super) struct TextStyleBinding {
+    style: TextStyle,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TextStyleBinding {
+    pub fn style(&self) -> &TextStyle {
+        &self.style
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Instance 0 is the shadow and instance 1 the text. Instances are drawn
+    /// in order so the shadow ends up underneath.
+    pub fn instances(&self) -> std::ops::Range<u32> {
+        if self.style.has_shadow() { 0..2 } else { 1..2 }
+    }
+}
+
+/// Identifies a font in a [`FontRegistry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+struct RegisteredFont {
+    font: Handle<Font>,
+    bind_group: wgpu::BindGroup,
 }
 
-pub struct TextBuffer {
-    // todo: font: FontId,
-    num_indices: u32,
-    indices: wgpu::Buffer,
-    vertices: wgpu::Buffer,
+/// Fonts available for drawing text, each with the bind group for its pages.
+pub struct FontRegistry {
+    fonts: Vec<RegisteredFont>,
+    names: HashMap<String, FontId>,
+}
+
+impl FontRegistry {
+    pub fn new() -> Self {
+        Self {
+            fonts: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Waits for `font` to load and makes it available as `name`. Registering
+    /// a name again replaces the font, keeping its id, so text buffers using
+    /// it pick up the new font the next time they are updated.
+    pub fn register(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline: &TextPipeline,
+        name: impl Into<String>,
+        font: Handle<Font>,
+    ) -> anyhow::Result<FontId> {
+        let name = name.into();
+        let bind_group = pipeline.bind_font(
+            device,
+            font.wait()
+                .with_context(|| format!("Unable to register font {name}"))?,
+        );
+        let registered = RegisteredFont { font, bind_group };
+
+        if let Some(&id) = self.names.get(&name) {
+            self.fonts[id.0] = registered;
+            return Ok(id);
+        }
+
+        let id = FontId(self.fonts.len());
+        self.fonts.push(registered);
+        self.names.insert(name, id);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: FontId) -> &Font {
+        self.fonts[id.0]
+            .font
+            .get()
+            .expect("fonts are loaded when registered")
+    }
+
+    fn bind_group(&self, id: FontId) -> &wgpu::BindGroup {
+        &self.fonts[id.0].bind_group
+    }
 }
 
 pub struct Font {
     unknown_char: char,
     pub info: FontData,
-    pub texture: wgpu::Texture,
+    /// One array layer per entry in [`FontData::pages`]
+    pub pages: Texture,
     pub glyph_map: HashMap<char, usize>,
+    pub kerning_map: HashMap<(char, char), i32>,
 }
 
 impl Font {
-    pub fn load(
-        resources: &impl Resources,
-        path: impl AsRef<Path>,
+    /// Used in place of characters the font doesn't have a glyph for.
+    pub const UNKNOWN_CHAR: char = '�';
+
+    /// Loads a zip holding a BMFont style `.json` descriptor and the page
+    /// images it names. Page paths are relative to the descriptor.
+    pub fn from_bytes(
+        bin: Vec<u8>,
         unknown_char: char,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) -> anyhow::Result<Self> {
-        let bin = resources.load_binary(path)?;
-
         let mut zip = zip::ZipArchive::new(Cursor::new(bin))?;
 
-        let mut buffer = Vec::new();
-
-        let texture = {
-            let mut zipped_img = zip.by_index(1)?;
-            let name = zipped_img.mangled_name();
-            zipped_img.read_to_end(&mut buffer)?;
-            let img = image::load_from_memory(&buffer)?.to_rgba8();
-
-            let dimensions = img.dimensions();
-            let texture_size = wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            };
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                label: Some(&format!("{}", name.display())),
-                view_formats: &[],
-            });
-
-            queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &img,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * dimensions.0),
-                    rows_per_image: Some(dimensions.1),
-                },
-                texture_size,
-            );
-
-            texture
-        };
-
-        buffer.clear();
-
-        zip.by_index(0)?.read_to_end(&mut buffer)?;
+        let json_name = zip
+            .file_names()
+            .find(|name| name.ends_with(".json"))
+            .context("Font has no .json descriptor")?
+            .to_owned();
+        let dir = json_name
+            .rsplit_once('/')
+            .map(|(dir, _)| format!("{dir}/"))
+            .unwrap_or_default();
+
+        let mut json = String::new();
+        zip.by_name(&json_name)?.read_to_string(&mut json)?;
+        let info: FontData =
+            serde_json::from_str(&json).with_context(|| format!("Unable to parse {json_name}"))?;
+
+        if info.pages.is_empty() {
+            anyhow::bail!("Font has no pages");
+        }
+        if let Some(glyph) = info
+            .glyphs
+            .iter()
+            .find(|g| g.page as usize >= info.pages.len())
+        {
+            anyhow::bail!("'{}' is on missing page {}", glyph.char, glyph.page);
+        }
 
-        let json = String::from_utf8(buffer)?;
-        let info: FontData = serde_json::from_str(&json)?;
+        let mut buffer = Vec::new();
+        let mut images = Vec::with_capacity(info.pages.len());
+        for page in &info.pages {
+            let path = format!("{dir}{page}");
+            buffer.clear();
+            zip.by_name(&path)
+                .with_context(|| format!("Font page {path} is missing"))?
+                .read_to_end(&mut buffer)?;
+            images.push(image::load_from_memory(&buffer)?.to_rgba8());
+        }
+        let pages = Texture::array_from_images(device, queue, &images, Some(&info.info.face))
+            .context("Unable to create font pages")?;
 
         let mut glyph_map = HashMap::new();
         for (i, glyph) in info.glyphs.iter().enumerate() {
@@ -372,20 +565,44 @@ impl Font {
             anyhow::bail!("'{unknown_char}' not supported by font");
         }
 
+        let kerning_map = info
+            .kernings
+            .iter()
+            .filter_map(|k| {
+                Some((
+                    (char::from_u32(k.first)?, char::from_u32(k.second)?),
+                    k.amount,
+                ))
+            })
+            .collect();
+
         Ok(Self {
             unknown_char,
-            texture,
+            pages,
             info,
             glyph_map,
+            kerning_map,
         })
     }
 
-    pub fn glyph(&self, c: char) -> Option<&Glyph> {
-        self.glyph_map.get(&c).map(|&i| &self.info.glyphs[i])
+    pub fn glyph_index(&self, c: char) -> Option<usize> {
+        self.glyph_map.get(&c).copied()
+    }
+
+    pub fn unknown_char(&self) -> char {
+        self.unknown_char
     }
 
-    pub fn unknown_glyph(&self) -> &Glyph {
-        self.glyph(self.unknown_char).unwrap()
+    /// Extra advance between `a` and `b` in font units.
+    pub fn kerning(&self, a: char, b: char) -> f32 {
+        self.kerning_map.get(&(a, b)).copied().unwrap_or(0) as f32
+    }
+}
+
+impl Asset for Font {
+    fn load(ctx: &LoadContext<'_>, bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let (device, queue) = ctx.gpu()?;
+        Self::from_bytes(bytes, Self::UNKNOWN_CHAR, device, queue)
     }
 }
 
@@ -398,6 +615,15 @@ pub struct FontData {
     pub common: FontCommonInfo,
     #[serde(rename = "distanceField")]
     pub distance_field: DistanceFieldInfo,
+    #[serde(default)]
+    pub kernings: Vec<Kerning>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Kerning {
+    pub first: u32,
+    pub second: u32,
+    pub amount: i32,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -460,3 +686,115 @@ pub struct DistanceFieldInfo {
     #[serde(rename = "distanceRange")]
     pub distance_range: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::render::{
+        assets::AssetManager, bindings::CameraBinder, resources::InMemoryResources, test_device,
+    };
+
+    /// A zip with a font descriptor in `font/` and a page image for each
+    /// entry in `glyph_pages`, which has the page of each glyph in order.
+    fn font_zip(face: &str, pages: u32, glyph_pages: &[u32]) -> Vec<u8> {
+        let chars = [Font::UNKNOWN_CHAR, 'a', 'b', 'c'];
+        let glyphs = glyph_pages
+            .iter()
+            .zip(chars)
+            .enumerate()
+            .map(|(i, (&page, c))| {
+                serde_json::json!({
+                    "id": c as u32, "index": i, "page": page, "char": c.to_string(),
+                    "width": 8, "height": 8, "x": 0, "y": 0,
+                    "xoffset": 0, "yoffset": 0, "xadvance": 8, "chnl": 15,
+                })
+            })
+            .collect::<Vec<_>>();
+        let page_names = (0..pages)
+            .map(|page| format!("page{page}.png"))
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "pages": page_names,
+            "chars": glyphs,
+            "info": {
+                "face": face, "size": 8, "bold": 0, "italic": 0, "charset": chars,
+                "unicode": 1, "stretchH": 100, "smooth": 1, "aa": 1,
+                "padding": [0, 0, 0, 0], "spacing": [0, 0],
+            },
+            "common": {
+                "lineHeight": 10, "base": 8, "scaleW": 16, "scaleH": 16, "pages": pages,
+                "packed": 0, "alphaChnl": 0, "redChnl": 0, "greenChnl": 0, "blueChnl": 0,
+            },
+            "distanceField": { "fieldType": "msdf", "distanceRange": 4 },
+        });
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("font/font.json", options).unwrap();
+        zip.write_all(json.to_string().as_bytes()).unwrap();
+        for name in &page_names {
+            let mut png = Cursor::new(Vec::new());
+            image::RgbaImage::new(16, 16)
+                .write_to(&mut png, image::ImageFormat::Png)
+                .unwrap();
+            zip.start_file(format!("font/{name}"), options).unwrap();
+            zip.write_all(png.get_ref()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn fonts_load_every_page() {
+        let (device, queue) = test_device();
+        let bytes = font_zip("Paged", 3, &[0, 1, 2, 2]);
+        let font = Font::from_bytes(bytes, Font::UNKNOWN_CHAR, &device, &queue).unwrap();
+
+        assert_eq!(font.pages.layers(), 3);
+        let page = |c| font.info.glyphs[font.glyph_index(c).unwrap()].page;
+        assert_eq!([page('a'), page('b'), page('c')], [1, 2, 2]);
+    }
+
+    #[test]
+    fn glyphs_on_missing_pages_fail_to_load() {
+        let (device, queue) = test_device();
+        let bytes = font_zip("Broken", 2, &[0, 2]);
+
+        let error = Font::from_bytes(bytes, Font::UNKNOWN_CHAR, &device, &queue)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("missing page 2"));
+    }
+
+    #[test]
+    fn registered_fonts_resolve_by_id() {
+        let (device, queue) = test_device();
+        let resources = InMemoryResources::new()
+            .with("one.zip", font_zip("One", 1, &[0, 0]))
+            .with("two.zip", font_zip("Two", 2, &[0, 1]))
+            .with("three.zip", font_zip("Three", 1, &[0]));
+        let assets = AssetManager::new(resources, &device, &queue);
+        let pipeline = TextPipeline::new(
+            &device,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            &CameraBinder::new(&device),
+        )
+        .unwrap();
+        let mut fonts = FontRegistry::new();
+        let mut register = |name, path| {
+            let font = assets.load_blocking::<Font>(path).unwrap();
+            fonts.register(&device, &pipeline, name, font).unwrap()
+        };
+
+        let one = register("body", "one.zip");
+        let two = register("title", "two.zip");
+        assert_ne!(one, two);
+        // Registering a name again replaces its font but keeps the id
+        assert_eq!(register("body", "three.zip"), one);
+
+        assert_eq!(fonts.get(one).info.info.face, "Three");
+        assert_eq!(fonts.get(two).info.info.face, "Two");
+        assert_eq!(fonts.get(two).info.pages.len(), 2);
+    }
+}
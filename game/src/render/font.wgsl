@@ -3,6 +3,10 @@ struct TexturedVertex {
     position: vec2<f32>,
     @location(1)
     uv: vec2<f32>,
+    @location(2)
+    color: vec4<f32>,
+    @location(3)
+    page: u32,
 }
 
 struct CameraUniform {
@@ -15,13 +19,27 @@ var<uniform> camera: CameraUniform;
 
 @group(0)
 @binding(0)
-var font_texture: texture_2d<f32>;
+var font_pages: texture_2d_array<f32>;
 @group(0)
 @binding(1)
 var font_sampler: sampler;
 
 struct FontUniforms {
     unit_range: vec2<f32>,
+}
+
+@group(0)
+@binding(2)
+var<uniform> uniforms: FontUniforms;
+
+struct TextStyle {
+    outline_color: vec4<f32>,
+    shadow_color: vec4<f32>,
+    glow_color: vec4<f32>,
+    shadow_offset: vec2<f32>,
+    outline_width: f32,
+    shadow_softness: f32,
+    glow_width: f32,
     in_bias: f32,
     out_bias: f32,
     smoothness: f32,
@@ -31,38 +49,75 @@ struct FontUniforms {
 
 @group(2)
 @binding(0)
-var<uniform> uniforms: FontUniforms;
+var<uniform> style: TextStyle;
+
+const SHADOW_LAYER: u32 = 0u;
 
 struct VsOut {
     @builtin(position)
     frag_position: vec4<f32>,
     @location(0)
     uv: vec2<f32>,
+    @location(1)
+    color: vec4<f32>,
+    @location(2) @interpolate(flat)
+    page: u32,
+    @location(3) @interpolate(flat)
+    layer: u32,
 }
 
 @vertex
-fn textured(in: TexturedVertex) -> VsOut {
-    return VsOut(camera.view_proj * vec4(in.position, 0.0, 1.0), in.uv);
+fn textured(in: TexturedVertex, @builtin(instance_index) layer: u32) -> VsOut {
+    var position = in.position;
+    if layer == SHADOW_LAYER {
+        position += style.shadow_offset;
+    }
+    return VsOut(camera.view_proj * vec4(position, 0.0, 1.0), in.uv, in.color, in.page, layer);
 }
 
 @fragment
 fn msdf_text(vs: VsOut) -> @location(0) vec4<f32> {
     let width = screen_px_range(vs.uv);
-    var opacity = sample(vs.uv, width);
+    let sd = signed_distance(vs.uv, vs.page);
+    var opacity = contour(sd, width);
 
     let dscale = 0.345;
     let duv = dscale * (dpdx(vs.uv) + dpdy(vs.uv));
     let box = vec4(vs.uv - duv, vs.uv + duv);
-    let asum = sample(box.xy, width)
-        + sample(box.zw, width)
-        + sample(box.xw, width)
-        + sample(box.zy, width);
-    opacity = mix(opacity, (opacity + 0.5 * asum) / 3.0, uniforms.super_sample);
-    opacity = pow(opacity, uniforms.inv_gamma);
+    let asum = sample(box.xy, vs.page, width)
+        + sample(box.zw, vs.page, width)
+        + sample(box.xw, vs.page, width)
+        + sample(box.zy, vs.page, width);
+    opacity = mix(opacity, (opacity + 0.5 * asum) / 3.0, style.super_sample);
+    opacity = pow(opacity, style.inv_gamma);
 
-    let col = vec3(1.0);
+    // Distance from the edge of the glyph in screen pixels, positive inside
+    let dist = width * (sd - 0.5 + style.in_bias);
 
-    return vec4(col, opacity);
+    if vs.layer == SHADOW_LAYER {
+        let softness = max(style.shadow_softness, 0.5);
+        let shadow = smoothstep(-softness, softness, dist + style.outline_width);
+        return vec4(style.shadow_color.rgb, style.shadow_color.a * shadow * vs.color.a);
+    }
+
+    var color = vec4(style.glow_color.rgb, 0.0);
+    if style.glow_width > 0.0 {
+        color.a = style.glow_color.a * (1.0 - smoothstep(0.0, style.glow_width, -dist));
+    }
+    if style.outline_width > 0.0 {
+        let outline = clamp(dist + style.outline_width + 0.5, 0.0, 1.0);
+        color = over(vec4(style.outline_color.rgb, style.outline_color.a * outline), color);
+    }
+    color = over(vec4(vs.color.rgb, opacity), color);
+
+    return vec4(color.rgb, color.a * vs.color.a);
+}
+
+/// Blends `top` over `bottom`, both with straight alpha.
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    let rgb = top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a);
+    return vec4(rgb / max(alpha, 1e-5), alpha);
 }
 
 fn median(msd: vec3<f32>) -> f32 {
@@ -75,17 +130,19 @@ fn screen_px_range(uv: vec2<f32>) -> f32 {
 }
 
 fn contour(d: f32, width: f32) -> f32 {
-    let e = width * (d - 0.5 + uniforms.in_bias) + 0.5 + uniforms.out_bias;
+    let e = width * (d - 0.5 + style.in_bias) + 0.5 + style.out_bias;
     return mix(
         clamp(e, 0.0, 1.0),
         smoothstep(0.0, 1.0, e),
-        uniforms.smoothness
+        style.smoothness
     );
 }
 
-fn sample(uv: vec2<f32>, width: f32) -> f32 {
-    let msd = textureSample(font_texture, font_sampler, uv);
-    let sd = median(msd.rgb);
-    let opacity = contour(sd, width);
-    return opacity;
-}
\ No newline at end of file
+fn signed_distance(uv: vec2<f32>, page: u32) -> f32 {
+    let msd = textureSample(font_pages, font_sampler, uv, page);
+    return median(msd.rgb);
+}
+
+fn sample(uv: vec2<f32>, page: u32, width: f32) -> f32 {
+    return contour(signed_distance(uv, page), width);
+}
@@ -1,29 +1,92 @@
-mod bindings;
+pub mod assets;
+mod atlas;
+pub mod bindings;
 pub mod buffer;
-mod font;
+mod debug;
+pub mod font;
+mod post;
 mod quad;
 pub mod resources;
-mod uniform;
-mod utils;
+pub mod rich_text;
+mod shape;
+mod target;
+pub mod text_cache;
+pub mod text_layout;
+mod texture;
+pub mod uniform;
+pub mod utils;
 pub mod vertex;
 
 use std::sync::Arc;
 
 use anyhow::Context;
-use wgpu::util::DeviceExt;
 use winit::window::Window;
 
-use crate::render::{
-    bindings::{CameraBinder, TextureBinder},
-    buffer::BackedBuffer,
-    font::{Font, TextBuffer, TextPipeline},
-    quad::QuadPipeline,
-    resources::FsResources,
-    uniform::CameraData,
-    vertex::{InstanceColor2d, Vertex2d},
+pub use post::PostSettings;
+
+use crate::{
+    game::{
+        Game,
+        animation::{AnimationLibrary, AnimationPlayer},
+        camera::Camera2d,
+    },
+    render::{
+        assets::AssetManager,
+        atlas::AtlasBuilder,
+        bindings::{CameraBinder, TextureBinder},
+        buffer::BackedBuffer,
+        debug::DebugPipeline,
+        font::{Font, FontId, FontRegistry, TextPipeline, TextStyle},
+        post::{PostProcess, SCENE_FORMAT},
+        quad::QuadPipeline,
+        resources::Resources,
+        rich_text::{SpanStyle, TextSpan},
+        shape::ShapePipeline,
+        target::{MultisampleTarget, RenderTarget, SurfaceTarget, color_attachment},
+        text_cache::TextCache,
+        text_layout::{HAlign, TextLayout, VAlign},
+        uniform::CameraData,
+        utils::Rect,
+        vertex::{InstanceShape2d, InstanceSprite2d, LineVertex},
+    },
+    ui::DrawList,
 };
 
-const PLAYER_COLORS: &[glam::Vec4] = &[
+/// How finished frames wait for the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum PresentMode {
+    /// Vsync, frames queue up and wait their turn
+    Fifo,
+    /// Vsync, but a new frame replaces the queued one instead of waiting
+    Mailbox,
+    /// No vsync, lowest latency but may tear
+    Immediate,
+}
+
+impl PresentMode {
+    pub const ALL: [Self; 3] = [Self::Fifo, Self::Mailbox, Self::Immediate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fifo => "Vsync",
+            Self::Mailbox => "Mailbox",
+            Self::Immediate => "Immediate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceOptions {
+    pub present_mode: PresentMode,
+    /// Frames that can be queued before rendering waits. Lower is more
+    /// responsive, higher is smoother when frame times vary.
+    pub max_frame_latency: u32,
+}
+
+/// Size of debug labels, in pixels
+const DEBUG_TEXT_SIZE: f32 = 14.0;
+
+pub const PLAYER_COLORS: &[glam::Vec4] = &[
     glam::vec4(1.0, 0.0, 0.0, 1.0),
     glam::vec4(0.0, 1.0, 0.0, 1.0),
     glam::vec4(0.0, 0.0, 1.0, 1.0),
@@ -32,32 +95,64 @@ const PLAYER_COLORS: &[glam::Vec4] = &[
     glam::vec4(1.0, 0.0, 1.0, 1.0),
 ];
 
+
 pub struct Renderer {
-    pub(crate) window: Arc<Window>,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    surface: wgpu::Surface<'static>,
-    config: wgpu::wgt::SurfaceConfiguration<Vec<wgpu::TextureFormat>>,
-    is_surface_configured: bool,
+    target: RenderTarget,
 
     // game specific
+    /// Draws the UI
     quad_pipeline: QuadPipeline,
-    player_vertices: BackedBuffer<Vertex2d>,
-    player_indices: BackedBuffer<u32>,
-    player_instances: BackedBuffer<InstanceColor2d>,
-    pickup_instances: BackedBuffer<InstanceColor2d>,
-    camera_buffer: BackedBuffer<CameraData>,
-    camera_binding: bindings::CameraBinding,
-    player_texture_binding: bindings::TextureBinding,
-    font: Font,
+    /// Draws sprites in the world, which has a different format
+    scene_quad_pipeline: QuadPipeline,
+    post: PostProcess,
+    sprite_atlas_binding: bindings::TextureBinding,
+    clip_frames: Vec<Vec<glam::Vec4>>,
+    sprite_instances: BackedBuffer<InstanceSprite2d>,
+    shape_pipeline: ShapePipeline,
+    /// Drawn under the sprites
+    shape_instances: BackedBuffer<InstanceShape2d>,
+    /// Drawn last, over the UI
+    debug_pipeline: DebugPipeline,
+    debug_vertices: BackedBuffer<LineVertex>,
+    debug_indices: BackedBuffer<u32>,
+    /// Samples per pixel in the passes drawing the world and the UI
+    sample_count: u32,
+    /// Usable with both the target's format and [`SCENE_FORMAT`]
+    sample_counts: Vec<u32>,
+    /// Resolved into the scene texture, only when multisampling
+    scene_multisample: Option<MultisampleTarget>,
+    /// Resolved into the target, only when multisampling
+    output_multisample: Option<MultisampleTarget>,
+    /// Solid white part of the sprite atlas, for drawing UI rects
+    white_uv: glam::Vec4,
+    ui_instances: BackedBuffer<InstanceSprite2d>,
+    camera_binder: CameraBinder,
+    texture_binder: TextureBinder,
+    /// One per view of the game, split screen has more than one
+    view_cameras: Vec<ViewCamera>,
+    /// Views drawn this frame
+    active_views: usize,
+    assets: AssetManager,
+    fonts: FontRegistry,
+    ui_font: FontId,
     text_pipeline: TextPipeline,
-    score_text: TextBuffer,
+    text_cache: TextCache,
     ui_camera_buffer: BackedBuffer<CameraData>,
     ui_camera_binding: bindings::CameraBinding,
+    /// Read back the next frame that's drawn
+    capture: bool,
+    screenshot: Option<anyhow::Result<image::RgbaImage>>,
 }
 
 impl Renderer {
-    pub(crate) async fn new(window: Arc<Window>, resources: FsResources) -> anyhow::Result<Self> {
+    pub(crate) async fn new(
+        window: Arc<Window>,
+        resources: impl Resources + Send + Sync + 'static,
+        animations: Arc<AnimationLibrary>,
+        options: SurfaceOptions,
+    ) -> anyhow::Result<Self> {
         let instance = wgpu::Instance::new(&Default::default());
 
         let surface = instance.create_surface(window.clone())?;
@@ -69,224 +164,808 @@ impl Renderer {
             })
             .await?;
 
-        let (device, queue) = adapter.request_device(&Default::default()).await?;
+        let (device, queue) = request_device(&adapter).await?;
+
+        let target = RenderTarget::Surface(SurfaceTarget::new(
+            window, surface, &adapter, &device, options,
+        )?);
+        Self::with_target(&adapter, device, queue, target, resources, animations)
+    }
 
-        let config = surface
-            .get_default_config(
-                &adapter,
-                window.inner_size().width.max(1),
-                window.inner_size().height.max(1),
-            )
-            .with_context(|| "Unable to get default surface config")?;
+    /// A renderer without a window that draws into a `width` by `height`
+    /// texture, which the golden image test below compares against a saved
+    /// image. Falls back to a software adapter when there's no GPU, like on
+    /// CI.
+    #[cfg(test)]
+    pub(crate) async fn headless(
+        resources: impl Resources + Send + Sync + 'static,
+        animations: Arc<AnimationLibrary>,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let adapter = headless_adapter().await?;
+        let (device, queue) = request_device(&adapter).await?;
+
+        let target = RenderTarget::Offscreen(target::OffscreenTarget::new(
+            &device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ));
+        Self::with_target(&adapter, device, queue, target, resources, animations)
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        surface.configure(&device, &config);
+    fn with_target(
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        target: RenderTarget,
+        resources: impl Resources + Send + Sync + 'static,
+        animations: Arc<AnimationLibrary>,
+    ) -> anyhow::Result<Self> {
+        let format = target.format();
+        let (width, height) = target.size();
 
         let camera_binder = CameraBinder::new(&device);
         let texture_binder = TextureBinder::new(&device);
 
-        let quad_pipeline =
-            QuadPipeline::new(&device, config.format, &camera_binder, &texture_binder);
+        let sample_counts = supported_sample_counts(adapter, &device, &[format, SCENE_FORMAT]);
 
-        let player_vertices = BackedBuffer::with_data(
-            &device,
-            vec![
-                Vertex2d::new(glam::vec2(-5.0, -5.0), glam::vec2(0.0, 0.0)),
-                Vertex2d::new(glam::vec2(5.0, -5.0), glam::vec2(1.0, 0.0)),
-                Vertex2d::new(glam::vec2(5.0, 5.0), glam::vec2(1.0, 1.0)),
-                Vertex2d::new(glam::vec2(-5.0, 5.0), glam::vec2(0.0, 1.0)),
-            ],
-            wgpu::BufferUsages::VERTEX,
-        );
-        let player_indices =
-            BackedBuffer::with_data(&device, vec![0, 1, 2, 0, 2, 3], wgpu::BufferUsages::INDEX);
-        let player_instances = BackedBuffer::with_capacity(&device, 8, wgpu::BufferUsages::VERTEX);
-        let pickup_instances =
+        let quad_pipeline = QuadPipeline::new(&device, format, &camera_binder, &texture_binder, 1)?;
+        let scene_quad_pipeline =
+            QuadPipeline::new(&device, SCENE_FORMAT, &camera_binder, &texture_binder, 1)?;
+        let post = PostProcess::new(&device, format, width, height)?;
+
+        let sprite_instances =
             BackedBuffer::with_capacity(&device, 128, wgpu::BufferUsages::VERTEX);
 
-        let camera_buffer = BackedBuffer::with_data(
-            &device,
-            vec![CameraData::IDENTITY],
-            wgpu::BufferUsages::UNIFORM,
-        );
-        let camera_binding = camera_binder.bind(&device, &camera_buffer);
+        let shape_pipeline = ShapePipeline::new(&device, SCENE_FORMAT, &camera_binder, 1)?;
+        let shape_instances = BackedBuffer::with_capacity(&device, 32, wgpu::BufferUsages::VERTEX);
 
-        let ui_camera_buffer = BackedBuffer::with_data(
-            &device,
-            vec![CameraData::IDENTITY],
-            wgpu::BufferUsages::UNIFORM,
-        );
+        let debug_pipeline = DebugPipeline::new(&device, format, &camera_binder, 1)?;
+        let debug_vertices = BackedBuffer::with_capacity(&device, 256, wgpu::BufferUsages::VERTEX);
+        let debug_indices = BackedBuffer::with_capacity(&device, 256, wgpu::BufferUsages::INDEX);
+
+        let mut ui_camera_data = CameraData::IDENTITY;
+        ui_camera_data.update(&ui_camera(width, height));
+        let ui_camera_buffer =
+            BackedBuffer::with_data(&device, vec![ui_camera_data], wgpu::BufferUsages::UNIFORM);
         let ui_camera_binding = camera_binder.bind(&device, &ui_camera_buffer);
 
-        let player_texture = device.create_texture_with_data(
-            &queue,
-            &wgpu::TextureDescriptor {
-                label: None,
-                size: wgpu::Extent3d {
-                    width: 1,
-                    height: 1,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            },
-            wgpu::wgt::TextureDataOrder::MipMajor,
-            &[255, 255, 255, 255],
+        let assets = AssetManager::new(resources, &device, &queue);
+
+        let sprite_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        // Sheets decode in parallel on background threads
+        let images = animations
+            .sheets()
+            .iter()
+            .map(|sheet| assets.load::<image::RgbaImage>(&sheet.image))
+            .collect::<Vec<_>>();
+        log::debug!("Waiting on {:?}", assets.pending());
+        let mut atlas_builder = AtlasBuilder::new();
+        for (sheet, image) in animations.sheets().iter().zip(&images) {
+            let image = image.wait()?;
+            animations.check_sheet_size(sheet, image.width(), image.height())?;
+            atlas_builder.add_sheet(&sheet.name, image, sheet.frame_width, sheet.frame_height);
+        }
+        atlas_builder.add(
+            "white",
+            image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])),
         );
-        let player_texture_view = player_texture.create_view(&Default::default());
-        let default_sampler = device.create_sampler(&Default::default());
-        let player_texture_binding =
-            texture_binder.bind(&device, &player_texture_view, &default_sampler);
+        let sprite_atlas = atlas_builder.build(&device, &queue, Some("sprite_atlas"))?;
+
+        // uv rects for each frame of each clip, indexed by ClipId
+        let clip_frames = animations
+            .clips()
+            .iter()
+            .map(|clip| {
+                clip.frames
+                    .iter()
+                    .map(|frame| {
+                        sprite_atlas
+                            .region(&format!("{}/{}", clip.sheet, frame.index))
+                            .map(|region| region.uv_rect)
+                            .with_context(|| {
+                                format!("Frame {} is outside sheet {}", frame.index, clip.sheet)
+                            })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-        let font = Font::load(&resources, "fonts/OpenSans MSDF.zip", '�', &device, &queue)?;
-        let text_pipeline = TextPipeline::new(
+        let white_uv = sprite_atlas
+            .region("white")
+            .context("White region missing from sprite atlas")?
+            .uv_rect;
+        let ui_instances = BackedBuffer::with_capacity(&device, 64, wgpu::BufferUsages::VERTEX);
+
+        let sprite_atlas_binding =
+            texture_binder.bind(&device, sprite_atlas.texture().view(), &sprite_sampler);
+
+        let text_pipeline = TextPipeline::new(&device, format, &camera_binder)?;
+        let mut fonts = FontRegistry::new();
+        let ui_font = fonts.register(
             &device,
-            &font,
-            config.format,
-            &camera_binder,
-            &texture_binder,
+            &text_pipeline,
+            "OpenSans",
+            assets.load_blocking::<Font>("fonts/OpenSans MSDF.zip")?,
         )?;
 
-        let score_text = text_pipeline.buffer_text(&font, &device, "Press a button to start")?;
+        let text_cache = TextCache::new();
 
         Ok(Self {
             device,
             queue,
-            window,
-            surface,
-            config,
-            is_surface_configured: cfg!(not(target_arch = "wasm32")),
+            target,
             quad_pipeline,
-            player_vertices,
-            player_indices,
-            player_instances,
-            pickup_instances,
-            camera_buffer,
-            camera_binding,
+            scene_quad_pipeline,
+            post,
+            sprite_atlas_binding,
+            clip_frames,
+            sprite_instances,
+            shape_pipeline,
+            shape_instances,
+            debug_pipeline,
+            debug_vertices,
+            debug_indices,
+            sample_count: 1,
+            sample_counts,
+            scene_multisample: None,
+            output_multisample: None,
+            white_uv,
+            ui_instances,
+            camera_binder,
+            texture_binder,
+            view_cameras: Vec::new(),
+            active_views: 0,
             ui_camera_buffer,
             ui_camera_binding,
-            player_texture_binding,
-            font,
+            assets,
+            fonts,
+            ui_font,
             text_pipeline,
-            score_text,
+            text_cache,
+            capture: false,
+            screenshot: None,
         })
     }
 
-    pub(crate) fn render(&mut self, game: &crate::game::Game) -> bool {
-        if !self.is_surface_configured {
-            self.surface.configure(&self.device, &self.config);
-            self.is_surface_configured = true;
+    /// Draws the world of `game`, if there is one, with `ui` over it.
+    pub(crate) fn render(&mut self, game: Option<&Game>, ui: &DrawList) -> bool {
+        let (frame, view) = match self.target.acquire(&self.device) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return true,
+            Err(e) => {
+                log::error!("{e:#}");
+                return false;
+            }
+        };
+
+        self.draw(&view, game, ui);
+        if std::mem::take(&mut self.capture) {
+            self.screenshot = Some(self.target.read_frame(&frame, &self.device, &self.queue));
         }
+        frame.present();
+
+        self.assets.collect_garbage();
+
+        true
+    }
+
+    /// Reads back the next frame [`Self::render`] draws, as it was before
+    /// it's presented.
+    pub(crate) fn capture_next_frame(&mut self) {
+        self.capture = true;
+    }
+
+    /// The frame asked for with [`Self::capture_next_frame`], once it's been
+    /// drawn.
+    pub(crate) fn take_screenshot(&mut self) -> Option<anyhow::Result<image::RgbaImage>> {
+        self.screenshot.take()
+    }
+
+    fn draw(&mut self, view: &wgpu::TextureView, game: Option<&Game>, ui: &DrawList) {
+        {
+            self.sprite_instances.clear();
+            self.shape_instances.clear();
+
+            self.prepare_ui(ui);
+            self.prepare_debug(game);
+            self.text_cache.prepare(&self.device, &self.queue);
+            let stats = self.text_cache.take_stats();
+            if stats.layouts > 0 || stats.uploads > 0 {
+                log::debug!("text cache: {stats:?}");
+            }
 
-        let frame = match self.surface.get_current_texture() {
-            Ok(frame) => frame,
-            Err(e) => match e {
-                wgpu::SurfaceError::Outdated => {
-                    return true;
+            let mut sprites = Vec::new();
+            let mut shapes = Vec::new();
+            if let Some(game) = game {
+                // First so they're under everything else
+                for particle in game.particles().particles() {
+                    shapes.push(
+                        InstanceShape2d::circle(particle.position, particle.size())
+                            .with_color(particle.color()),
+                    );
                 }
-                e => {
-                    log::error!("{e}");
-                    return false;
+
+                for (i, player) in game.players().iter().enumerate() {
+                    let color = PLAYER_COLORS[i % PLAYER_COLORS.len()];
+                    // Sticks out of the body in the direction it's heading
+                    if player.joystick != glam::Vec2::ZERO {
+                        let tip = player.joystick.normalize() * (player.size + 3.0);
+                        shapes.push(
+                            InstanceShape2d::capsule(player.position, player.position + tip, 2.0)
+                                .with_color((color.truncate() * 0.4).extend(1.0)),
+                        );
+                    }
+                    // The body is the collision circle, the sprite adds a face
+                    shapes.push(
+                        InstanceShape2d::circle(player.position, player.size)
+                            .with_color(color)
+                            .with_outline(1.5, (color.truncate() * 0.4).extend(1.0)),
+                    );
+                    sprites.push(
+                        InstanceSprite2d::new(
+                            player.position,
+                            glam::Vec2::splat(player.size * 2.0),
+                            self.frame_uv(&player.animation),
+                        )
+                        .with_color(color)
+                        .with_layer(1.0),
+                    );
                 }
-            },
-        };
 
-        let view = frame.texture.create_view(&Default::default());
+                for pickup in game.pickups() {
+                    // Makes them easier to spot against the background
+                    shapes.push(
+                        InstanceShape2d::ring(pickup.position, 8.0, 1.0)
+                            .with_color(glam::vec4(1.0, 0.9, 0.4, 0.6)),
+                    );
+                    sprites.push(InstanceSprite2d::new(
+                        pickup.position,
+                        glam::vec2(10.0, 10.0),
+                        self.frame_uv(&pickup.animation),
+                    ));
+                }
+            }
+
+            // No depth buffer, so draw order puts higher layers on top
+            sprites.sort_by(|a, b| a.layer().total_cmp(&b.layer()));
 
-        {
-            self.player_instances.clear();
-
-            let mut instances_batch = self.player_instances.batch(&self.device, &self.queue);
-            let mut score_text = String::new();
-
-            for (i, player) in game.players().iter().enumerate() {
-                instances_batch.push(InstanceColor2d::new(
-                    player.position,
-                    PLAYER_COLORS[i % PLAYER_COLORS.len()],
-                ));
-                score_text += &format!("Player {}: {}\n", i + 1, player.score);
-                self.text_pipeline.update_text(
-                    &self.font,
-                    &score_text,
-                    &mut self.score_text,
-                    &self.device,
-                    &self.queue,
-                );
+            {
+                let mut batch = self.shape_instances.batch(&self.device, &self.queue);
+                for shape in shapes {
+                    batch.push(shape);
+                }
+            }
+
+            let mut batch = self.sprite_instances.batch(&self.device, &self.queue);
+            for sprite in sprites {
+                batch.push(sprite);
             }
         }
 
         {
-            self.pickup_instances.clear();
-            let mut batch = self.pickup_instances.batch(&self.device, &self.queue);
-            for pickup in game.pickups() {
-                batch.push(InstanceColor2d::new(
-                    pickup.position,
-                    glam::Vec4::splat(1.0),
-                ));
+            let (width, height) = self.target.size();
+            let screen = glam::vec2(width as f32, height as f32);
+            let views = game.map_or(&[][..], |game| game.views());
+            for (i, view) in views.iter().enumerate() {
+                if i == self.view_cameras.len() {
+                    let buffer = BackedBuffer::with_data(
+                        &self.device,
+                        vec![CameraData::IDENTITY],
+                        wgpu::BufferUsages::UNIFORM,
+                    );
+                    let binding = self.camera_binder.bind(&self.device, &buffer);
+                    self.view_cameras.push(ViewCamera {
+                        buffer,
+                        binding,
+                        viewport: Rect::ZERO,
+                    });
+                }
+                let camera = &mut self.view_cameras[i];
+                camera
+                    .buffer
+                    .update(&self.queue, |data| data[0].update(&view.camera));
+                camera.viewport = Rect::new(
+                    (view.viewport.min * screen).round(),
+                    (view.viewport.max * screen).round(),
+                );
             }
+            self.active_views = views.len();
         }
 
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+
+        // The world goes into the scene texture for post-processing
         {
-            self.camera_buffer
-                .update(&self.queue, |data| data[0].update(game.active_camera()));
-            self.ui_camera_buffer
-                .update(&self.queue, |data| data[0].update(game.ui_camera()));
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("scene"),
+                color_attachments: &[Some(color_attachment(
+                    self.post.scene(),
+                    self.scene_multisample.as_ref(),
+                ))],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            for camera in &self.view_cameras[..self.active_views] {
+                if !camera.set_viewport(&mut pass) {
+                    continue;
+                }
+                self.shape_pipeline
+                    .draw(&mut pass, &camera.binding, &self.shape_instances);
+                self.scene_quad_pipeline.draw_sprites(
+                    &mut pass,
+                    &camera.binding,
+                    &self.sprite_atlas_binding,
+                    &self.sprite_instances,
+                );
+            }
         }
 
-        let mut encoder = self.device.create_command_encoder(&Default::default());
+        self.post.prepare(&self.queue, &mut encoder);
 
         {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
+                label: Some("composite"),
+                color_attachments: &[Some(color_attachment(
+                    view,
+                    self.output_multisample.as_ref(),
+                ))],
                 depth_stencil_attachment: None,
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
 
-            self.quad_pipeline.draw(
+            self.post.composite(&mut pass);
+
+            self.quad_pipeline.draw_sprites(
                 &mut pass,
-                &self.camera_binding,
-                &self.player_texture_binding,
-                &self.player_vertices,
-                &self.player_indices,
-                &self.player_instances,
+                &self.ui_camera_binding,
+                &self.sprite_atlas_binding,
+                &self.ui_instances,
             );
 
-            self.quad_pipeline.draw(
+            self.text_cache.draw(
                 &mut pass,
-                &self.camera_binding,
-                &self.player_texture_binding,
-                &self.player_vertices,
-                &self.player_indices,
-                &self.pickup_instances,
+                &self.text_pipeline,
+                &self.fonts,
+                &self.ui_camera_binding,
             );
 
-            self.text_pipeline
-                .draw_text(&mut pass, &self.score_text, &self.ui_camera_binding);
+            for camera in &self.view_cameras[..self.active_views] {
+                if camera.set_viewport(&mut pass) {
+                    self.debug_pipeline.draw(
+                        &mut pass,
+                        &camera.binding,
+                        &self.debug_vertices,
+                        &self.debug_indices,
+                    );
+                }
+            }
         }
 
         self.queue.submit([encoder.finish()]);
-        frame.present();
+    }
 
-        true
+    fn prepare_ui(&mut self, ui: &DrawList) {
+        self.ui_instances.clear();
+        {
+            let mut batch = self.ui_instances.batch(&self.device, &self.queue);
+            for quad in &ui.quads {
+                batch.push(
+                    InstanceSprite2d::new(quad.rect.center(), quad.rect.size(), self.white_uv)
+                        .with_color(quad.color),
+                );
+            }
+        }
+
+        // Keeps HUD text readable where it overlaps the players
+        let outlined = TextStyle::default()
+            .with_outline(1.5, glam::vec4(0.0, 0.0, 0.0, 1.0))
+            .with_shadow(glam::vec2(2.0, 2.0), glam::vec4(0.0, 0.0, 0.0, 0.6));
+
+        for text in &ui.texts {
+            let style = if text.outlined {
+                outlined
+            } else {
+                TextStyle::default()
+            };
+            self.text_cache.queue(
+                &self.device,
+                &self.text_pipeline,
+                &self.fonts,
+                self.ui_font,
+                &text.spans,
+                &text.layout,
+                style,
+            );
+        }
+    }
+
+    /// Debug lines go in a buffer drawn with each view's camera, labels are
+    /// queued as text at their position in each view.
+    fn prepare_debug(&mut self, game: Option<&Game>) {
+        self.debug_vertices.clear();
+        self.debug_indices.clear();
+        let Some(game) = game else {
+            return;
+        };
+        let debug = game.debug();
+
+        {
+            let mut batch = self.debug_vertices.batch_indexed(
+                &self.device,
+                &self.queue,
+                &mut self.debug_indices,
+            );
+            for line in debug.lines() {
+                batch.line(
+                    LineVertex::new(line.a, line.color),
+                    LineVertex::new(line.b, line.color),
+                );
+            }
+        }
+
+        let (width, height) = self.target.size();
+        let screen = glam::vec2(width as f32, height as f32);
+        let style = TextStyle::default().with_outline(1.5, glam::vec4(0.0, 0.0, 0.0, 1.0));
+        for text in debug.texts() {
+            for view in game.views() {
+                let position = view.world_to_screen(text.position, screen);
+                let viewport = Rect::new(view.viewport.min * screen, view.viewport.max * screen);
+                if !viewport.contains(position) {
+                    continue;
+                }
+                let span = TextSpan::new(
+                    text.text.clone(),
+                    SpanStyle::default()
+                        .with_color(text.color)
+                        .with_size(DEBUG_TEXT_SIZE),
+                );
+                self.text_cache.queue(
+                    &self.device,
+                    &self.text_pipeline,
+                    &self.fonts,
+                    self.ui_font,
+                    &[span],
+                    &TextLayout::at(position).align(HAlign::Center, VAlign::Middle),
+                    style,
+                );
+            }
+        }
+    }
+
+    fn frame_uv(&self, animation: &AnimationPlayer) -> glam::Vec4 {
+        self.clip_frames[animation.clip().index()][animation.frame_index()]
+    }
+
+    pub(crate) fn set_surface_options(&mut self, options: SurfaceOptions) {
+        self.target.set_options(&self.device, options);
+    }
+
+    pub(crate) fn set_post_process(&mut self, settings: PostSettings) {
+        self.post.set_settings(settings);
+    }
+
+    /// Multisamples with the most samples the adapter supports, up to
+    /// `samples`. 1 turns it off.
+    pub(crate) fn set_msaa_samples(&mut self, samples: u32) -> anyhow::Result<()> {
+        let sample_count = self
+            .sample_counts
+            .iter()
+            .copied()
+            .filter(|&count| count <= samples)
+            .max()
+            .unwrap_or(1);
+        if sample_count != samples {
+            log::warn!("{samples}x MSAA isn't supported, using {sample_count}x");
+        }
+        if sample_count == self.sample_count {
+            return Ok(());
+        }
+
+        // Everything drawn in the same pass has to agree on the sample count,
+        // so nothing is replaced until every pipeline built
+        let format = self.target.format();
+        let quad_pipeline = QuadPipeline::new(
+            &self.device,
+            format,
+            &self.camera_binder,
+            &self.texture_binder,
+            sample_count,
+        )?;
+        let scene_quad_pipeline = QuadPipeline::new(
+            &self.device,
+            SCENE_FORMAT,
+            &self.camera_binder,
+            &self.texture_binder,
+            sample_count,
+        )?;
+        let shape_pipeline = ShapePipeline::new(
+            &self.device,
+            SCENE_FORMAT,
+            &self.camera_binder,
+            sample_count,
+        )?;
+        let debug_pipeline =
+            DebugPipeline::new(&self.device, format, &self.camera_binder, sample_count)?;
+        let text_pipeline = self
+            .text_pipeline
+            .create_pipeline(&self.device, sample_count)?;
+        let composite_pipeline = self
+            .post
+            .create_composite_pipeline(&self.device, sample_count)?;
+
+        self.quad_pipeline = quad_pipeline;
+        self.scene_quad_pipeline = scene_quad_pipeline;
+        self.shape_pipeline = shape_pipeline;
+        self.debug_pipeline = debug_pipeline;
+        self.text_pipeline.set_pipeline(text_pipeline);
+        self.post.set_composite_pipeline(composite_pipeline);
+        self.sample_count = sample_count;
+        self.create_multisample_targets();
+        Ok(())
+    }
+
+    fn create_multisample_targets(&mut self) {
+        let (width, height) = self.target.size();
+        let target = |format| {
+            (self.sample_count > 1).then(|| {
+                MultisampleTarget::new(&self.device, format, width, height, self.sample_count)
+            })
+        };
+        self.scene_multisample = target(SCENE_FORMAT);
+        self.output_multisample = target(self.target.format());
     }
 
     pub(crate) fn resize(&mut self, width: u32, height: u32) {
-        self.is_surface_configured = true;
-        self.config.width = width.max(1);
-        self.config.height = height.max(1);
-        self.surface.configure(&self.device, &self.config);
+        self.target.resize(&self.device, width, height);
+        self.create_multisample_targets();
+
+        let (width, height) = self.target.size();
+        self.post.resize(&self.device, width, height);
+        let camera = ui_camera(width, height);
+        self.ui_camera_buffer
+            .update(&self.queue, |data| data[0].update(&camera));
+    }
+}
+
+/// Camera uniforms for one view of the game.
+struct ViewCamera {
+    buffer: BackedBuffer<CameraData>,
+    binding: bindings::CameraBinding,
+    /// In pixels
+    viewport: Rect,
+}
+
+impl ViewCamera {
+    /// Limits drawing to this view, or returns false if it's empty.
+    fn set_viewport(&self, pass: &mut wgpu::RenderPass<'_>) -> bool {
+        let viewport = self.viewport;
+        let (width, height) = (viewport.width(), viewport.height());
+        if width <= 0.0 || height <= 0.0 {
+            return false;
+        }
+        let min = viewport.min;
+        pass.set_viewport(min.x, min.y, width, height, 0.0, 1.0);
+        pass.set_scissor_rect(min.x as u32, min.y as u32, width as u32, height as u32);
+        true
+    }
+}
+
+/// Any adapter, falling back to a software one when there's no GPU, like on
+/// CI.
+#[cfg(test)]
+async fn headless_adapter() -> anyhow::Result<wgpu::Adapter> {
+    let instance = wgpu::Instance::new(&Default::default());
+    match instance.request_adapter(&Default::default()).await {
+        Ok(adapter) => Ok(adapter),
+        Err(e) => {
+            log::warn!("No hardware adapter ({e}), trying a software one");
+            Ok(instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    force_fallback_adapter: true,
+                    ..Default::default()
+                })
+                .await?)
+        }
+    }
+}
+
+/// A device for tests that need one but don't render.
+#[cfg(test)]
+pub(crate) fn test_device() -> (wgpu::Device, wgpu::Queue) {
+    pollster::block_on(async {
+        let adapter = headless_adapter().await.unwrap();
+        request_device(&adapter).await.unwrap()
+    })
+}
+
+/// Asks for the adapter's own format features when it has them, without
+/// them only 4x multisampling is allowed.
+async fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let required_features =
+        adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+    Ok(adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            required_features,
+            ..Default::default()
+        })
+        .await?)
+}
+
+/// Sample counts out of 1, 2, 4 and 8 that every format in `formats` can be
+/// drawn and resolved with.
+fn supported_sample_counts(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    formats: &[wgpu::TextureFormat],
+) -> Vec<u32> {
+    let adapter_specific = device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&count| {
+            formats.iter().all(|&format| {
+                let flags = if adapter_specific {
+                    adapter.get_texture_format_features(format).flags
+                } else {
+                    format.guaranteed_format_features(device.features()).flags
+                };
+                count == 1
+                    || (flags.sample_count_supported(count)
+                        && flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE))
+            })
+        })
+        .collect()
+}
+
+/// Maps one unit to one pixel, with the origin in the top left and y down.
+fn ui_camera(width: u32, height: u32) -> Camera2d {
+    let width = width as f32;
+    let height = -(height as f32);
+    Camera2d::new(width, height, glam::vec2(width * 0.5, height * -0.5))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        game::{Input, InputEvent},
+        render::resources::{FsResources, Resources},
+        ui::{Panel, Ui, UiState},
+    };
+
+    const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/scene.png");
+
+    /// Channels can be off by this much, eg. from a GPU rounding differently
+    /// than the software adapter the image was made with.
+    const TOLERANCE: u8 = 16;
+    /// Fraction of pixels allowed to be further off than that
+    const MAX_DIFFERENT: f32 = 0.01;
+
+    /// Two players walking away from each other with debug drawing on, and a
+    /// panel of UI in the middle.
+    fn scene(width: u32, height: u32) -> (Renderer, Game, DrawList) {
+        let resources = FsResources::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res"));
+        let animations = Arc::new(
+            AnimationLibrary::from_json(&resources.load_string("animations.json").unwrap())
+                .unwrap(),
+        );
+        let renderer = pollster::block_on(Renderer::headless(
+            resources,
+            animations.clone(),
+            width,
+            height,
+        ))
+        .unwrap();
+
+        // No pickups or particles, which are placed randomly
+        let mut game = Game::new(Duration::from_secs(3600), animations).unwrap();
+        game.set_particle_budget(0);
+        game.set_debug_draw(true);
+        game.resize(width, height);
+        for (x, y) in [(1.0, 0.5), (-1.0, -0.5)] {
+            let id = game.spawn_player();
+            game.handle_input(InputEvent {
+                id,
+                input: Input::X(x),
+            });
+            game.handle_input(InputEvent {
+                id,
+                input: Input::Y(y),
+            });
+        }
+        game.tick(Duration::from_millis(500));
+
+        let mut state = UiState::default();
+        let screen = Rect::new(glam::Vec2::ZERO, glam::vec2(width as f32, height as f32));
+        let mut ui = Ui::new(&mut state, &[], screen);
+        Panel::new(160.0).show(&mut ui, |ui| {
+            ui.title("[color=yellow]Golden[/color]");
+            ui.button("Button");
+        });
+        (renderer, game, ui.finish())
+    }
+
+    impl Renderer {
+        /// Draws a frame the way F12 captures one.
+        fn render_to_image(
+            &mut self,
+            game: Option<&Game>,
+            ui: &DrawList,
+        ) -> anyhow::Result<image::RgbaImage> {
+            self.capture_next_frame();
+            anyhow::ensure!(self.render(game, ui), "Unable to render");
+            self.take_screenshot().context("No frame was captured")?
+        }
+    }
+
+    fn render_scene(width: u32, height: u32) -> image::RgbaImage {
+        let (mut renderer, game, ui) = scene(width, height);
+        renderer.render_to_image(Some(&game), &ui).unwrap()
+    }
+
+    #[test]
+    fn only_the_next_frame_is_captured() {
+        let (mut renderer, game, ui) = scene(160, 90);
+        assert!(renderer.take_screenshot().is_none());
+        renderer.capture_next_frame();
+        assert!(renderer.render(Some(&game), &ui));
+        assert!(renderer.take_screenshot().unwrap().is_ok());
+        assert!(renderer.render(Some(&game), &ui));
+        assert!(renderer.take_screenshot().is_none());
+    }
+
+    #[test]
+    fn msaa_changes_between_frames() {
+        let (mut renderer, game, ui) = scene(160, 90);
+        let before = renderer.render_to_image(Some(&game), &ui).unwrap();
+        for samples in [8, 4, 2, 1] {
+            renderer.set_msaa_samples(samples).unwrap();
+            assert!(renderer.sample_count <= samples);
+            assert!(renderer.sample_counts.contains(&renderer.sample_count));
+            renderer.render_to_image(Some(&game), &ui).unwrap();
+        }
+        assert_eq!(renderer.render_to_image(Some(&game), &ui).unwrap(), before);
+    }
+
+    #[test]
+    fn matches_golden_image() {
+        let image = render_scene(480, 270);
+        // Run with UPDATE_GOLDEN set after changing what's drawn, and check
+        // the new image by eye before committing it
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(std::path::Path::new(GOLDEN).parent().unwrap()).unwrap();
+            image.save(GOLDEN).unwrap();
+            return;
+        }
+
+        let golden = image::open(GOLDEN).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), golden.dimensions());
+        let different = image
+            .pixels()
+            .zip(golden.pixels())
+            .filter(|(a, b)| a.0.iter().zip(b.0).any(|(&a, b)| a.abs_diff(b) > TOLERANCE))
+            .count();
+        let allowed = (MAX_DIFFERENT * (image.width() * image.height()) as f32) as usize;
+        if different > allowed {
+            let actual = std::env::temp_dir().join("scene.png");
+            image.save(&actual).unwrap();
+            panic!(
+                "{different} pixels differ from {GOLDEN}, only {allowed} may. Saved the render to {}",
+                actual.display()
+            );
+        }
     }
 }
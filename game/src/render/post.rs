@@ -0,0 +1,424 @@
+//! Effects applied to the world after it's drawn. The world is drawn into an
+//! HDR texture, then composited into the target with bloom, chromatic
+//! aberration, vignette and CRT scanlines. The UI is drawn after, untouched.
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::render::utils::RenderPipelineBuilder;
+
+/// What the world is drawn into before post-processing.
+pub const SCENE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct PostSettings {
+    pub bloom: bool,
+    /// Brightness things start glowing at, from 0 to 1
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    /// Darkening toward the corners, 0 turns it off
+    pub vignette: f32,
+    /// How far colors split apart toward the edges, 0 turns it off
+    pub chromatic_aberration: f32,
+    /// Scanlines and a curved screen
+    pub crt: bool,
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            bloom: true,
+            bloom_threshold: 0.6,
+            bloom_intensity: 0.8,
+            vignette: 0.3,
+            chromatic_aberration: 0.0,
+            crt: false,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
+struct PostUniforms {
+    texel: [f32; 2],
+    direction: [f32; 2],
+    threshold: f32,
+    bloom: f32,
+    vignette: f32,
+    aberration: f32,
+    crt: f32,
+    _padding: [f32; 3],
+}
+
+/// Passes in the order they run.
+#[derive(Debug, Clone, Copy)]
+enum Pass {
+    /// Scene to `bloom[0]`, at half size
+    Bright,
+    /// `bloom[0]` to `bloom[1]`
+    BlurHorizontal,
+    /// `bloom[1]` back to `bloom[0]`
+    BlurVertical,
+    /// Scene and `bloom[0]` to the target
+    Composite,
+}
+
+impl Pass {
+    const ALL: [Self; 4] = [
+        Self::Bright,
+        Self::BlurHorizontal,
+        Self::BlurVertical,
+        Self::Composite,
+    ];
+}
+
+/// Textures sized to the target, remade when it resizes.
+struct PostTextures {
+    scene: wgpu::TextureView,
+    bloom: [wgpu::TextureView; 2],
+    /// One per [`Pass`]
+    bind_groups: Vec<wgpu::BindGroup>,
+    size: (u32, u32),
+}
+
+pub struct PostProcess {
+    layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    /// Of the pass the composite draws into
+    format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    bright_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    /// One per [`Pass`]
+    uniforms: Vec<wgpu::Buffer>,
+    textures: PostTextures,
+    settings: PostSettings,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("PostProcess"),
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(3),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("post.wgsl"));
+        let pipeline = |entry_point, format| {
+            fullscreen_pipeline(device, &pipeline_layout, &shader, entry_point, format, 1)
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniforms = Pass::ALL
+            .iter()
+            .map(|pass| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("PostProcess::{pass:?}")),
+                    size: size_of::<PostUniforms>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect::<Vec<_>>();
+        let textures = PostTextures::new(device, &layout, &sampler, &uniforms, width, height);
+
+        Ok(Self {
+            bright_pipeline: pipeline("fs_bright", SCENE_FORMAT)?,
+            blur_pipeline: pipeline("fs_blur", SCENE_FORMAT)?,
+            composite_pipeline: pipeline("fs_composite", format)?,
+            layout,
+            pipeline_layout,
+            shader,
+            format,
+            sampler,
+            uniforms,
+            textures,
+            settings: PostSettings::default(),
+        })
+    }
+
+    /// Where the world should be drawn.
+    pub fn scene(&self) -> &wgpu::TextureView {
+        &self.textures.scene
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.textures.size != (width, height) {
+            self.textures = PostTextures::new(
+                device,
+                &self.layout,
+                &self.sampler,
+                &self.uniforms,
+                width,
+                height,
+            );
+        }
+    }
+
+    /// Builds the composite for passes with `sample_count` samples, to swap
+    /// in with [`PostProcess::set_composite_pipeline`]. The passes before it
+    /// aren't multisampled.
+    pub fn create_composite_pipeline(
+        &self,
+        device: &wgpu::Device,
+        sample_count: u32,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        fullscreen_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            "fs_composite",
+            self.format,
+            sample_count,
+        )
+    }
+
+    pub fn set_composite_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.composite_pipeline = pipeline;
+    }
+
+    pub fn set_settings(&mut self, settings: PostSettings) {
+        self.settings = settings;
+    }
+
+    /// Runs the passes before the composite, which draws into the pass the
+    /// UI is drawn in.
+    pub fn prepare(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        self.write_uniforms(queue);
+        if !self.settings.bloom {
+            return;
+        }
+
+        let [first, second] = &self.textures.bloom;
+        let passes = [
+            (Pass::Bright, &self.bright_pipeline, first),
+            (Pass::BlurHorizontal, &self.blur_pipeline, second),
+            (Pass::BlurVertical, &self.blur_pipeline, first),
+        ];
+        for (pass, pipeline, target) in passes {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&format!("PostProcess::{pass:?}")),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.textures.bind_groups[pass as usize], &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Draws the post-processed scene over the whole pass.
+    pub fn composite(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_pipeline(&self.composite_pipeline);
+        pass.set_bind_group(0, &self.textures.bind_groups[Pass::Composite as usize], &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn write_uniforms(&self, queue: &wgpu::Queue) {
+        let settings = self.settings;
+        let (width, height) = self.textures.size;
+        let full = [1.0 / width as f32, 1.0 / height as f32];
+        let half = full.map(|texel| texel * 2.0);
+        for pass in Pass::ALL {
+            let uniforms = match pass {
+                Pass::Bright => PostUniforms {
+                    texel: full,
+                    threshold: settings.bloom_threshold,
+                    ..Default::default()
+                },
+                Pass::BlurHorizontal => PostUniforms {
+                    texel: half,
+                    direction: [1.0, 0.0],
+                    ..Default::default()
+                },
+                Pass::BlurVertical => PostUniforms {
+                    texel: half,
+                    direction: [0.0, 1.0],
+                    ..Default::default()
+                },
+                Pass::Composite => PostUniforms {
+                    texel: full,
+                    bloom: if settings.bloom {
+                        settings.bloom_intensity
+                    } else {
+                        0.0
+                    },
+                    vignette: settings.vignette,
+                    aberration: settings.chromatic_aberration,
+                    crt: if settings.crt { 1.0 } else { 0.0 },
+                    ..Default::default()
+                },
+            };
+            queue.write_buffer(
+                &self.uniforms[pass as usize],
+                0,
+                bytemuck::bytes_of(&uniforms),
+            );
+        }
+    }
+}
+
+fn fullscreen_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> anyhow::Result<wgpu::RenderPipeline> {
+    RenderPipelineBuilder::new()
+        .label(entry_point)
+        .layout(layout)
+        .vertex(wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_fullscreen"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        })
+        .fragment(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        })
+        .multisample(sample_count)
+        .build(device)
+}
+
+impl PostTextures {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        uniforms: &[wgpu::Buffer],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = |label, width: u32, height: u32| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: width.max(1),
+                        height: height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: SCENE_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        };
+        let scene = texture("post_scene", width, height);
+        let bloom = [
+            texture("post_bloom_0", width / 2, height / 2),
+            texture("post_bloom_1", width / 2, height / 2),
+        ];
+
+        // The second texture is only read by the composite, the others just
+        // need something that isn't the pass's target
+        let sources = |pass| match pass {
+            Pass::Bright => (&scene, &bloom[1]),
+            Pass::BlurHorizontal => (&bloom[0], &scene),
+            Pass::BlurVertical => (&bloom[1], &scene),
+            Pass::Composite => (&scene, &bloom[0]),
+        };
+        let bind_groups = Pass::ALL
+            .iter()
+            .map(|&pass| {
+                let (source, second) = sources(pass);
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(&format!("PostProcess::{pass:?}")),
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: uniforms[pass as usize].as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(second),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        Self {
+            scene,
+            bloom,
+            bind_groups,
+            size: (width, height),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+struct PostUniforms {
+    // Size of one texel of `source`, in uv
+    texel: vec2<f32>,
+    // Blur direction, in texels
+    direction: vec2<f32>,
+    threshold: f32,
+    bloom: f32,
+    vignette: f32,
+    aberration: f32,
+    crt: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+}
+
+struct VsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    uv: vec2<f32>,
+}
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var samp: sampler;
+
+@group(0)
+@binding(2)
+var<uniform> post: PostUniforms;
+
+// Only read by the composite pass
+@group(0)
+@binding(3)
+var bloom: texture_2d<f32>;
+
+// One triangle covering the screen, uvs are y down like textures
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> VsOut {
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+    return VsOut(vec4(uv * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0), uv);
+}
+
+fn sample(texture: texture_2d<f32>, uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(texture, samp, uv, 0.0).rgb;
+}
+
+// Halves the resolution and keeps what's brighter than the threshold, with
+// a soft knee so glow doesn't pop in
+@fragment
+fn fs_bright(vs: VsOut) -> @location(0) vec4<f32> {
+    let offset = post.texel * 0.5;
+    let color = (sample(source, vs.uv + vec2(-offset.x, -offset.y))
+        + sample(source, vs.uv + vec2(offset.x, -offset.y))
+        + sample(source, vs.uv + vec2(-offset.x, offset.y))
+        + sample(source, vs.uv + vec2(offset.x, offset.y))) * 0.25;
+
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = post.threshold * 0.5;
+    var soft = clamp(brightness - post.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    let contribution = max(soft, brightness - post.threshold) / max(brightness, 1e-4);
+    return vec4(color * contribution, 1.0);
+}
+
+// One direction of a 9 tap gaussian blur
+@fragment
+fn fs_blur(vs: VsOut) -> @location(0) vec4<f32> {
+    let weights = array(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    let step = post.direction * post.texel;
+    var color = sample(source, vs.uv) * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        color += sample(source, vs.uv + offset) * weights[i];
+        color += sample(source, vs.uv - offset) * weights[i];
+    }
+    return vec4(color, 1.0);
+}
+
+@fragment
+fn fs_composite(vs: VsOut) -> @location(0) vec4<f32> {
+    // Bulges the picture like a CRT's curved glass
+    var centered = vs.uv * 2.0 - 1.0;
+    centered *= 1.0 + dot(centered, centered) * 0.05 * post.crt;
+    let uv = centered * 0.5 + 0.5;
+
+    // Splits colors apart toward the edges
+    let split = (uv - 0.5) * post.aberration * 0.01;
+    var color = vec3(
+        sample(source, uv + split).r,
+        sample(source, uv).g,
+        sample(source, uv - split).b,
+    );
+    color += sample(bloom, uv) * post.bloom;
+
+    let edge = length(vs.uv - 0.5) * 1.41421;
+    color *= 1.0 - post.vignette * smoothstep(0.4, 1.2, edge);
+
+    // Darkens every other row of pixels
+    let scanline = f32(u32(vs.frag_position.y) % 2u);
+    color *= 1.0 - post.crt * 0.2 * scanline;
+
+    // Curving pulls the edges in from outside the picture
+    let inside = all(uv >= vec2(0.0)) && all(uv <= vec2(1.0));
+    return vec4(select(vec3(0.0), color, inside), 1.0);
+}
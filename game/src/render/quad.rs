@@ -3,11 +3,14 @@ use wgpu::BlendState;
 use crate::render::{
     bindings::{CameraBinder, CameraBinding, TextureBinder, TextureBinding},
     buffer::BackedBuffer,
-    vertex::{InstanceColor2d, Vertex2d},
+    utils::RenderPipelineBuilder,
+    vertex::{InstanceSprite2d, Vertex2d},
 };
 
 pub struct QuadPipeline {
-    pipeline: wgpu::RenderPipeline,
+    sprite_pipeline: wgpu::RenderPipeline,
+    unit_vertices: BackedBuffer<Vertex2d>,
+    unit_indices: BackedBuffer<u32>,
 }
 
 impl QuadPipeline {
@@ -16,60 +19,82 @@ impl QuadPipeline {
         format: wgpu::TextureFormat,
         camera_binder: &CameraBinder,
         texture_binder: &TextureBinder,
-    ) -> Self {
+        sample_count: u32,
+    ) -> anyhow::Result<Self> {
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
             bind_group_layouts: &[camera_binder.layout(), texture_binder.layout()],
             push_constant_ranges: &[],
         });
         let shader = device.create_shader_module(wgpu::include_wgsl!("quad.wgsl"));
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("QuadPipeline"),
-            layout: Some(&layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &[Vertex2d::VERTEX_LAYOUT, InstanceColor2d::VERTEX_LAYOUT],
-            },
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-            cache: None,
-        });
-        Self { pipeline }
+        let build = |label, entry_point, instance_layout| {
+            RenderPipelineBuilder::new()
+                .label(label)
+                .layout(&layout)
+                .vertex(wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    buffers: &[Vertex2d::VERTEX_LAYOUT, instance_layout],
+                })
+                .fragment(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                })
+                .multisample(sample_count)
+                .build(device)
+        };
+        let sprite_pipeline = build(
+            "QuadPipeline::sprite_pipeline",
+            "vs_sprite",
+            InstanceSprite2d::VERTEX_LAYOUT,
+        )?;
+
+        // Images are y down, the world is y up
+        let unit_vertices = BackedBuffer::with_data(
+            device,
+            vec![
+                Vertex2d::new(glam::vec2(-0.5, -0.5), glam::vec2(0.0, 1.0)),
+                Vertex2d::new(glam::vec2(0.5, -0.5), glam::vec2(1.0, 1.0)),
+                Vertex2d::new(glam::vec2(0.5, 0.5), glam::vec2(1.0, 0.0)),
+                Vertex2d::new(glam::vec2(-0.5, 0.5), glam::vec2(0.0, 0.0)),
+            ],
+            wgpu::BufferUsages::VERTEX,
+        );
+        let unit_indices =
+            BackedBuffer::with_data(device, vec![0, 1, 2, 0, 2, 3], wgpu::BufferUsages::INDEX);
+
+        Ok(Self {
+            sprite_pipeline,
+            unit_vertices,
+            unit_indices,
+        })
     }
 
-    pub fn draw<'a, 'b: 'a>(
+    /// Draws sprites from the atlas bound to `texture` in one draw call.
+    pub fn draw_sprites<'a, 'b: 'a>(
         &'a self,
         pass: &'a mut wgpu::RenderPass<'b>,
         camera: &'a CameraBinding,
         texture: &'a TextureBinding,
-        vertices: &'a BackedBuffer<Vertex2d>,
-        indices: &'a BackedBuffer<u32>,
-        instances: &'a BackedBuffer<InstanceColor2d>,
+        instances: &'a BackedBuffer<InstanceSprite2d>,
     ) {
         if instances.len() == 0 {
             return;
         }
 
-        pass.set_pipeline(&self.pipeline);
+        pass.set_pipeline(&self.sprite_pipeline);
         pass.set_bind_group(0, camera.bind_group(), &[]);
         pass.set_bind_group(1, texture.bind_group(), &[]);
-        pass.set_index_buffer(indices.slice(), wgpu::IndexFormat::Uint32);
-        pass.set_vertex_buffer(0, vertices.slice());
+        pass.set_index_buffer(self.unit_indices.slice(), wgpu::IndexFormat::Uint32);
+        pass.set_vertex_buffer(0, self.unit_vertices.slice());
         pass.set_vertex_buffer(1, instances.slice());
-        pass.draw_indexed(0..indices.len(), 0, 0..instances.len());
+        pass.draw_indexed(0..self.unit_indices.len(), 0, 0..instances.len());
     }
 }
@@ -7,10 +7,18 @@ struct Vertex2d {
     uv: vec2<f32>,
 }
 
-struct InstanceColor2d {
+struct InstanceSprite2d {
     @location(2)
     position: vec2<f32>,
     @location(3)
+    scale: vec2<f32>,
+    @location(4)
+    rotation: f32,
+    @location(5)
+    layer: f32,
+    @location(6)
+    uv_rect: vec4<f32>,
+    @location(7)
     color: vec4<f32>,
 }
 
@@ -39,11 +47,16 @@ var tex: texture_2d<f32>;
 @binding(1)
 var samp: sampler;
 
+// Expects a unit quad centered on the origin with uvs from 0 to 1
 @vertex
-fn vs_main(vertex: Vertex2d, instance: InstanceColor2d) -> VsOut {
+fn vs_sprite(vertex: Vertex2d, instance: InstanceSprite2d) -> VsOut {
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let local = vertex.position * instance.scale;
+    let rotated = vec2(local.x * c - local.y * s, local.x * s + local.y * c);
     return VsOut(
-        camera.view_proj * vec4(vertex.position + instance.position, 0.0, 1.0),
-        vertex.uv,
+        camera.view_proj * vec4(rotated + instance.position, 0.0, 1.0),
+        mix(instance.uv_rect.xy, instance.uv_rect.zw, vertex.uv),
         instance.color,
     );
 }
@@ -1,8 +1,12 @@
 use std::{
+    collections::HashMap,
     fs,
     path::{Path, PathBuf},
+    sync::{Arc, RwLock},
 };
 
+use anyhow::Context;
+
 // pub mod buffer;
 // pub mod camera;
 // pub mod font;
@@ -15,6 +19,25 @@ use std::{
 pub trait Resources {
     fn load_string(&self, path: impl AsRef<Path>) -> anyhow::Result<String>;
     fn load_binary(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>>;
+
+    /// Async variant of [`Resources::load_string`]. The default implementation
+    /// runs the blocking version when first polled, which is fine for tasks
+    /// started with `App::spawn_task` as those run on their own thread.
+    fn load_string_async(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> impl Future<Output = anyhow::Result<String>> {
+        async move { self.load_string(path) }
+    }
+
+    /// Async variant of [`Resources::load_binary`], see
+    /// [`Resources::load_string_async`].
+    fn load_binary_async(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> impl Future<Output = anyhow::Result<Vec<u8>>> {
+        async move { self.load_binary(path) }
+    }
 }
 
 #[derive(Clone)]
@@ -32,16 +55,236 @@ impl FsResources {
 
 impl Resources for FsResources {
     fn load_binary(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
-        // TODO: WASM
+        // TODO: WASM, fetch over HTTP in `load_binary_async` instead
         let full_path = self.base_dir.join(path);
         log::info!("load_binary({})", full_path.display());
-        Ok(fs::read(full_path)?)
+        fs::read(&full_path).with_context(|| format!("Unable to read {}", full_path.display()))
     }
 
     fn load_string(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
-        // TODO: WASM
+        // TODO: WASM, fetch over HTTP in `load_string_async` instead
         let full_path = self.base_dir.join(path);
         log::info!("load_string({})", full_path.display());
-        Ok(fs::read_to_string(full_path)?)
+        fs::read_to_string(&full_path)
+            .with_context(|| format!("Unable to read {}", full_path.display()))
+    }
+}
+
+/// Map backed resources. Cloning is cheap and clones share the same files, so
+/// tests can hand a copy to a loader and keep inserting into the original.
+#[derive(Clone, Default)]
+pub struct InMemoryResources {
+    files: Arc<RwLock<HashMap<PathBuf, Arc<[u8]>>>>,
+}
+
+/// Files compiled into the executable, by path relative to `game/res`
+const EMBEDDED: &[(&str, &[u8])] = &[
+    (
+        "animations.json",
+        include_bytes!("../../res/animations.json"),
+    ),
+    (
+        "fonts/OpenSans MSDF.zip",
+        include_bytes!("../../res/fonts/OpenSans MSDF.zip"),
+    ),
+    (
+        "sprites/pickup.png",
+        include_bytes!("../../res/sprites/pickup.png"),
+    ),
+    (
+        "sprites/player.png",
+        include_bytes!("../../res/sprites/player.png"),
+    ),
+];
+
+impl InMemoryResources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The game's resources as they were when it was built, for running
+    /// without the `res` directory and on targets without a file system.
+    pub fn embedded() -> Self {
+        EMBEDDED
+            .iter()
+            .fold(Self::new(), |resources, &(path, data)| {
+                resources.with(path, data)
+            })
+    }
+
+    pub fn with(self, path: impl AsRef<Path>, data: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, data);
+        self
+    }
+
+    pub fn insert(&self, path: impl AsRef<Path>, data: impl Into<Vec<u8>>) {
+        self.files
+            .write()
+            .unwrap()
+            .insert(path.as_ref().to_owned(), data.into().into());
+    }
+}
+
+impl Resources for InMemoryResources {
+    fn load_binary(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+        let path = path.as_ref();
+        self.files
+            .read()
+            .unwrap()
+            .get(path)
+            .map(|data| data.to_vec())
+            .with_context(|| format!("No resource at {}", path.display()))
+    }
+
+    fn load_string(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
+        Ok(String::from_utf8(self.load_binary(path)?)?)
+    }
+}
+
+/// The resource directory if there is one, otherwise the embedded copies.
+#[derive(Clone)]
+pub enum GameResources {
+    Files(FsResources),
+    Embedded(InMemoryResources),
+}
+
+impl GameResources {
+    pub fn new(base_dir: impl AsRef<Path>) -> Self {
+        let base_dir = base_dir.as_ref();
+        if base_dir.is_dir() {
+            Self::Files(FsResources::new(base_dir))
+        } else {
+            log::warn!(
+                "{} not found, using the resources embedded at build time",
+                base_dir.display()
+            );
+            Self::Embedded(InMemoryResources::embedded())
+        }
+    }
+}
+
+impl Resources for GameResources {
+    fn load_string(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
+        match self {
+            Self::Files(resources) => resources.load_string(path),
+            Self::Embedded(resources) => resources.load_string(path),
+        }
+    }
+
+    fn load_binary(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Files(resources) => resources.load_binary(path),
+            Self::Embedded(resources) => resources.load_binary(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_strings_and_binaries() {
+        let resources = InMemoryResources::new()
+            .with("text.txt", "hello")
+            .with("data.bin", [0u8, 1, 255]);
+
+        assert_eq!(resources.load_string("text.txt").unwrap(), "hello");
+        assert_eq!(resources.load_binary("data.bin").unwrap(), [0, 1, 255]);
+        assert_eq!(resources.load_binary("text.txt").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn async_variants_match_blocking() {
+        let resources = InMemoryResources::new()
+            .with("text.txt", "hello")
+            .with("data.bin", [1u8, 2, 3]);
+
+        let bytes = pollster::block_on(resources.load_binary_async("data.bin")).unwrap();
+        assert_eq!(bytes, resources.load_binary("data.bin").unwrap());
+        let text = pollster::block_on(resources.load_string_async("text.txt")).unwrap();
+        assert_eq!(text, "hello");
+        assert!(pollster::block_on(resources.load_binary_async("missing.bin")).is_err());
+        assert!(pollster::block_on(resources.load_string_async("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn async_loads_wait_until_polled() {
+        let resources = InMemoryResources::new();
+
+        let binary = resources.load_binary_async("late.bin");
+        let string = resources.load_string_async("late.txt");
+        resources.insert("late.bin", [4u8]);
+        resources.insert("late.txt", "added after starting to load");
+        assert_eq!(pollster::block_on(binary).unwrap(), [4]);
+        assert_eq!(
+            pollster::block_on(string).unwrap(),
+            "added after starting to load"
+        );
+    }
+
+    #[test]
+    fn missing_paths_are_errors() {
+        let resources = InMemoryResources::new();
+
+        let error = resources.load_binary("missing.bin").unwrap_err();
+        assert!(error.to_string().contains("missing.bin"));
+        assert!(resources.load_string("missing.txt").is_err());
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error() {
+        let resources = InMemoryResources::new().with("data.bin", [0xffu8, 0xfe]);
+
+        assert!(resources.load_string("data.bin").is_err());
+        assert!(resources.load_binary("data.bin").is_ok());
+    }
+
+    #[test]
+    fn clones_share_files() {
+        let resources = InMemoryResources::new();
+        let loader = resources.clone();
+
+        resources.insert("late.txt", "added after cloning");
+        assert_eq!(
+            loader.load_string("late.txt").unwrap(),
+            "added after cloning"
+        );
+    }
+
+    #[test]
+    fn every_resource_is_embedded() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
+        let files = FsResources::new(&dir);
+        let embedded = InMemoryResources::embedded();
+
+        let mut dirs = vec![dir.clone()];
+        let mut count = 0;
+        while let Some(next) = dirs.pop() {
+            for entry in fs::read_dir(next).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let path = path.strip_prefix(&dir).unwrap();
+                assert_eq!(
+                    embedded.load_binary(path).ok(),
+                    files.load_binary(path).ok(),
+                    "{} isn't embedded or is out of date",
+                    path.display()
+                );
+                count += 1;
+            }
+        }
+        assert_eq!(count, EMBEDDED.len());
+    }
+
+    #[test]
+    fn missing_directories_use_the_embedded_resources() {
+        let resources = GameResources::new("not/a/directory");
+
+        assert!(matches!(resources, GameResources::Embedded(_)));
+        assert!(resources.load_string("animations.json").is_ok());
     }
 }
@@ -0,0 +1,279 @@
+//! Markup for styling runs of text, eg. `[color=red]Player 1[/color]`.
+//!
+//! Supported tags are `[color=name]` or `[color=#rrggbb]`, `[size=px]` and
+//! `[i]` for a faux italic. Tags nest and are closed with `[/color]`,
+//! `[/size]` and `[/i]`. Use `[[` for a literal `[`. Anything that isn't a
+//! known tag is drawn as is.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpanStyle {
+    pub color: glam::Vec4,
+    /// Size in pixels, or `None` for the font's native size
+    pub size: Option<f32>,
+    pub italic: bool,
+}
+
+impl Default for SpanStyle {
+    fn default() -> Self {
+        Self {
+            color: glam::Vec4::ONE,
+            size: None,
+            italic: false,
+        }
+    }
+}
+
+impl SpanStyle {
+    pub fn with_color(mut self, color: glam::Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+impl TextSpan {
+    pub fn new(text: impl Into<String>, style: SpanStyle) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+enum Tag {
+    Color(glam::Vec4),
+    Size(f32),
+    Italic,
+}
+
+impl Tag {
+    fn parse(tag: &str) -> Option<Self> {
+        match tag.split_once('=') {
+            Some(("color", value)) => parse_color(value).map(Self::Color),
+            Some(("size", value)) => value.parse().ok().map(Self::Size),
+            None if tag == "i" => Some(Self::Italic),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Color(_) => "color",
+            Self::Size(_) => "size",
+            Self::Italic => "i",
+        }
+    }
+
+    fn apply(&self, style: &mut SpanStyle) {
+        match *self {
+            Self::Color(color) => style.color = color,
+            Self::Size(size) => style.size = Some(size),
+            Self::Italic => style.italic = true,
+        }
+    }
+}
+
+/// Splits `text` into spans of the same style. Text outside of any tags uses
+/// `base`.
+pub fn parse_markup(text: &str, base: SpanStyle) -> Vec<TextSpan> {
+    let mut spans = Vec::new();
+    let mut stack: Vec<(&'static str, SpanStyle)> = Vec::new();
+    let mut style = base;
+    let mut current = String::new();
+    let mut rest = text;
+
+    let mut flush = |current: &mut String, style: SpanStyle| {
+        if !current.is_empty() {
+            spans.push(TextSpan::new(std::mem::take(current), style));
+        }
+    };
+
+    while let Some(start) = rest.find('[') {
+        current.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("[[") {
+            current.push('[');
+            rest = after;
+            continue;
+        }
+
+        let Some(end) = rest.find(']') else {
+            break;
+        };
+        let tag = &rest[1..end];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            if stack.last().is_some_and(|(open, _)| *open == name) {
+                flush(&mut current, style);
+                style = stack.pop().unwrap().1;
+                rest = &rest[end + 1..];
+                continue;
+            }
+        } else if let Some(tag) = Tag::parse(tag) {
+            flush(&mut current, style);
+            stack.push((tag.name(), style));
+            tag.apply(&mut style);
+            rest = &rest[end + 1..];
+            continue;
+        }
+
+        // Not a tag we understand, so draw it
+        current.push('[');
+        rest = &rest[1..];
+    }
+
+    current.push_str(rest);
+    flush(&mut current, style);
+
+    spans
+}
+
+/// Parses a named color or a `#rrggbb` / `#rrggbbaa` hex code.
+pub fn parse_color(value: &str) -> Option<glam::Vec4> {
+    if let Some(hex) = value.strip_prefix('#') {
+        // from_str_radix would also accept a sign
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .map(|c| c as f32 / 255.0)
+        };
+        return match hex.len() {
+            6 => Some(glam::vec4(channel(0)?, channel(2)?, channel(4)?, 1.0)),
+            8 => Some(glam::vec4(
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+                channel(6)?,
+            )),
+            _ => None,
+        };
+    }
+
+    let color = match value {
+        "white" => glam::vec4(1.0, 1.0, 1.0, 1.0),
+        "black" => glam::vec4(0.0, 0.0, 0.0, 1.0),
+        "red" => glam::vec4(1.0, 0.0, 0.0, 1.0),
+        "green" => glam::vec4(0.0, 1.0, 0.0, 1.0),
+        "blue" => glam::vec4(0.0, 0.0, 1.0, 1.0),
+        "yellow" => glam::vec4(1.0, 1.0, 0.0, 1.0),
+        "cyan" => glam::vec4(0.0, 1.0, 1.0, 1.0),
+        "magenta" => glam::vec4(1.0, 0.0, 1.0, 1.0),
+        "orange" => glam::vec4(1.0, 0.5, 0.0, 1.0),
+        "gray" | "grey" => glam::vec4(0.5, 0.5, 0.5, 1.0),
+        _ => return None,
+    };
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: glam::Vec4 = glam::vec4(1.0, 0.0, 0.0, 1.0);
+
+    fn span(text: &str, style: SpanStyle) -> TextSpan {
+        TextSpan::new(text, style)
+    }
+
+    fn italic(style: SpanStyle) -> SpanStyle {
+        SpanStyle {
+            italic: true,
+            ..style
+        }
+    }
+
+    #[test]
+    fn plain_text_is_one_span() {
+        let base = SpanStyle::default();
+        assert_eq!(parse_markup("hello", base), [span("hello", base)]);
+        assert_eq!(parse_markup("", base), []);
+    }
+
+    #[test]
+    fn tags_nest() {
+        let base = SpanStyle::default();
+        let red = base.with_color(RED);
+        let big_red = red.with_size(32.0);
+        let spans = parse_markup("a[color=red]b[size=32]c[i]d[/i][/size]e[/color]f", base);
+        assert_eq!(
+            spans,
+            [
+                span("a", base),
+                span("b", red),
+                span("c", big_red),
+                span("d", italic(big_red)),
+                span("e", red),
+                span("f", base),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_tags_run_to_the_end() {
+        let base = SpanStyle::default();
+        assert_eq!(
+            parse_markup("a[i]b", base),
+            [span("a", base), span("b", italic(base))]
+        );
+        // A `[` without a `]` is drawn as is
+        assert_eq!(
+            parse_markup("a[color=red", base),
+            [span("a[color=red", base)]
+        );
+    }
+
+    #[test]
+    fn unknown_and_mismatched_tags_are_drawn() {
+        let base = SpanStyle::default();
+        assert_eq!(parse_markup("[b]x[/b]", base), [span("[b]x[/b]", base)]);
+        assert_eq!(
+            parse_markup("[color=nope]x", base),
+            [span("[color=nope]x", base)]
+        );
+        assert_eq!(
+            parse_markup("[i]x[/color]", base),
+            [span("x[/color]", italic(base))]
+        );
+        assert_eq!(parse_markup("[/i]", base), [span("[/i]", base)]);
+    }
+
+    #[test]
+    fn double_brackets_escape() {
+        let base = SpanStyle::default();
+        assert_eq!(
+            parse_markup("[[i]x[[[i]y", base),
+            [span("[i]x[", base), span("y", italic(base))]
+        );
+    }
+
+    #[test]
+    fn colors_parse_from_names_and_hex() {
+        assert_eq!(parse_color("red"), Some(RED));
+        assert_eq!(parse_color("grey"), parse_color("gray"));
+        assert_eq!(parse_color("#ff0000"), Some(RED));
+        assert_eq!(parse_color("#ff000080"), Some(RED.with_w(128.0 / 255.0)));
+    }
+
+    #[test]
+    fn bad_colors_dont_parse() {
+        for value in [
+            "", "#", "#ff00", "#ff00000", "#gg0000", "#ff00zz", "#+f0000", "#ééé", "Red",
+        ] {
+            assert_eq!(parse_color(value), None, "{value}");
+        }
+    }
+}
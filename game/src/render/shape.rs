@@ -0,0 +1,90 @@
+use wgpu::BlendState;
+
+use crate::render::{
+    bindings::{CameraBinder, CameraBinding},
+    buffer::BackedBuffer,
+    utils::RenderPipelineBuilder,
+    vertex::{InstanceShape2d, Vertex2d},
+};
+
+/// Draws [`InstanceShape2d`]s, which need no texture.
+pub struct ShapePipeline {
+    pipeline: wgpu::RenderPipeline,
+    unit_vertices: BackedBuffer<Vertex2d>,
+    unit_indices: BackedBuffer<u32>,
+}
+
+impl ShapePipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_binder: &CameraBinder,
+        sample_count: u32,
+    ) -> anyhow::Result<Self> {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[camera_binder.layout()],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shape.wgsl"));
+        let pipeline = RenderPipelineBuilder::new()
+            .label("ShapePipeline")
+            .layout(&layout)
+            .vertex(wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[Vertex2d::VERTEX_LAYOUT, InstanceShape2d::VERTEX_LAYOUT],
+            })
+            .fragment(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .multisample(sample_count)
+            .build(device)?;
+
+        let unit_vertices = BackedBuffer::with_data(
+            device,
+            vec![
+                Vertex2d::new(glam::vec2(-0.5, -0.5), glam::vec2(0.0, 1.0)),
+                Vertex2d::new(glam::vec2(0.5, -0.5), glam::vec2(1.0, 1.0)),
+                Vertex2d::new(glam::vec2(0.5, 0.5), glam::vec2(1.0, 0.0)),
+                Vertex2d::new(glam::vec2(-0.5, 0.5), glam::vec2(0.0, 0.0)),
+            ],
+            wgpu::BufferUsages::VERTEX,
+        );
+        let unit_indices =
+            BackedBuffer::with_data(device, vec![0, 1, 2, 0, 2, 3], wgpu::BufferUsages::INDEX);
+
+        Ok(Self {
+            pipeline,
+            unit_vertices,
+            unit_indices,
+        })
+    }
+
+    /// Draws every shape in `instances` in one draw call.
+    pub fn draw<'a, 'b: 'a>(
+        &'a self,
+        pass: &'a mut wgpu::RenderPass<'b>,
+        camera: &'a CameraBinding,
+        instances: &'a BackedBuffer<InstanceShape2d>,
+    ) {
+        if instances.len() == 0 {
+            return;
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, camera.bind_group(), &[]);
+        pass.set_index_buffer(self.unit_indices.slice(), wgpu::IndexFormat::Uint32);
+        pass.set_vertex_buffer(0, self.unit_vertices.slice());
+        pass.set_vertex_buffer(1, instances.slice());
+        pass.draw_indexed(0..self.unit_indices.len(), 0, 0..instances.len());
+    }
+}
@@ -0,0 +1,105 @@
+struct Vertex2d {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    uv: vec2<f32>,
+}
+
+struct InstanceShape2d {
+    @location(2)
+    position: vec2<f32>,
+    @location(3)
+    half_size: vec2<f32>,
+    @location(4)
+    rotation: f32,
+    @location(5)
+    corner_radius: f32,
+    @location(6)
+    thickness: f32,
+    @location(7)
+    outline: f32,
+    @location(8)
+    color: vec4<f32>,
+    @location(9)
+    outline_color: vec4<f32>,
+}
+
+struct VsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    // Position relative to the shape's center, before rotating
+    @location(0)
+    local: vec2<f32>,
+    @location(1) @interpolate(flat)
+    half_size: vec2<f32>,
+    // Corner radius, thickness and outline
+    @location(2) @interpolate(flat)
+    params: vec3<f32>,
+    @location(3)
+    color: vec4<f32>,
+    @location(4)
+    outline_color: vec4<f32>,
+}
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> camera: Camera;
+
+// Room around the outline for the anti-aliased edge, in world units
+const EDGE_PADDING: f32 = 1.0;
+
+// Expects a unit quad centered on the origin
+@vertex
+fn vs_main(vertex: Vertex2d, instance: InstanceShape2d) -> VsOut {
+    let extent = instance.half_size + instance.outline + EDGE_PADDING;
+    let local = vertex.position * extent * 2.0;
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotated = vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+    return VsOut(
+        camera.view_proj * vec4(rotated + instance.position, 0.0, 1.0),
+        local,
+        instance.half_size,
+        vec3(instance.corner_radius, instance.thickness, instance.outline),
+        instance.color,
+        instance.outline_color,
+    );
+}
+
+// Negative inside the shape
+fn rounded_rect(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + radius;
+    return length(max(q, vec2(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// How much of a pixel is inside the edge, from the distance to it and the
+// distance covered by one pixel
+fn coverage(distance: f32, pixel: f32) -> f32 {
+    return clamp(0.5 - distance / pixel, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(vs: VsOut) -> @location(0) vec4<f32> {
+    let radius = vs.params.x;
+    let thickness = vs.params.y;
+    let outline = vs.params.z;
+
+    var distance = rounded_rect(vs.local, vs.half_size, radius);
+    if thickness > 0.0 {
+        // A band along the inside of the edge
+        distance = abs(distance + thickness * 0.5) - thickness * 0.5;
+    }
+    let pixel = max(fwidth(distance), 1e-4);
+
+    let fill = coverage(distance, pixel);
+    if outline <= 0.0 {
+        return vec4(vs.color.rgb, vs.color.a * fill);
+    }
+    let outer = coverage(distance - outline, pixel);
+    let color = mix(vs.outline_color, vs.color, fill);
+    return vec4(color.rgb, color.a * outer);
+}
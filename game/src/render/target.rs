@@ -0,0 +1,372 @@
+//! Where the renderer draws: a window's surface, or a texture in tests.
+//! Frames of either can be read back into an image, for screenshots and
+//! golden images.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use winit::window::Window;
+
+use crate::render::{PresentMode, SurfaceOptions};
+
+pub(crate) struct SurfaceTarget {
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    is_configured: bool,
+    /// Supported by the surface
+    present_modes: Vec<wgpu::PresentMode>,
+}
+
+impl SurfaceTarget {
+    pub(crate) fn new(
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        options: SurfaceOptions,
+    ) -> anyhow::Result<Self> {
+        let mut config = surface
+            .get_default_config(
+                adapter,
+                window.inner_size().width.max(1),
+                window.inner_size().height.max(1),
+            )
+            .with_context(|| "Unable to get default surface config")?;
+        let capabilities = surface.get_capabilities(adapter);
+        // Frames are copied out of for screenshots
+        config.usage |= capabilities.usages & wgpu::TextureUsages::COPY_SRC;
+        let present_modes = capabilities.present_modes;
+        config.present_mode = choose_present_mode(&present_modes, options.present_mode);
+        config.desired_maximum_frame_latency = options.max_frame_latency.max(1);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        surface.configure(device, &config);
+
+        Ok(Self {
+            surface,
+            config,
+            present_modes,
+            is_configured: cfg!(not(target_arch = "wasm32")),
+        })
+    }
+
+    /// Returns `Ok(None)` when this frame should be skipped, and only fails
+    /// when the app can't go on.
+    fn acquire(&mut self, device: &wgpu::Device) -> anyhow::Result<Option<wgpu::SurfaceTexture>> {
+        if !self.is_configured {
+            self.surface.configure(device, &self.config);
+            self.is_configured = true;
+        }
+
+        match self.surface.get_current_texture() {
+            Ok(frame) => Ok(Some(frame)),
+            // A resize is on its way
+            Err(wgpu::SurfaceError::Outdated) => Ok(None),
+            Err(wgpu::SurfaceError::Lost) => {
+                log::warn!("Surface lost, reconfiguring it");
+                self.is_configured = false;
+                Ok(None)
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                Err(wgpu::SurfaceError::OutOfMemory).context("Unable to acquire a frame")
+            }
+            Err(e @ (wgpu::SurfaceError::Timeout | wgpu::SurfaceError::Other)) => {
+                log::warn!("Skipping a frame: {e}");
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A texture to render into instead of a window.
+#[cfg(test)]
+pub struct OffscreenTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+#[cfg(test)]
+impl OffscreenTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+}
+
+/// A multisampled texture drawn into in place of another, which it's
+/// resolved into at the end of the pass.
+pub(crate) struct MultisampleTarget {
+    view: wgpu::TextureView,
+}
+
+impl MultisampleTarget {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("multisample_target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        Self { view }
+    }
+}
+
+/// Clears `view` and draws into it, through `multisample` when there is
+/// one. It has to be the same size and format as `view`.
+pub(crate) fn color_attachment<'a>(
+    view: &'a wgpu::TextureView,
+    multisample: Option<&'a MultisampleTarget>,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    let ops = |store| wgpu::Operations {
+        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+        store,
+    };
+    match multisample {
+        // The samples aren't needed once they're resolved
+        Some(multisample) => wgpu::RenderPassColorAttachment {
+            view: &multisample.view,
+            resolve_target: Some(view),
+            ops: ops(wgpu::StoreOp::Discard),
+        },
+        None => wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: ops(wgpu::StoreOp::Store),
+        },
+    }
+}
+
+pub(crate) enum RenderTarget {
+    Surface(SurfaceTarget),
+    #[cfg(test)]
+    Offscreen(OffscreenTarget),
+}
+
+/// What to draw into this frame.
+pub(crate) enum Frame {
+    Surface(wgpu::SurfaceTexture),
+    #[cfg(test)]
+    Offscreen,
+}
+
+impl RenderTarget {
+    pub(crate) fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            Self::Surface(target) => target.config.format,
+            #[cfg(test)]
+            Self::Offscreen(target) => target.texture.format(),
+        }
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        match self {
+            Self::Surface(target) => (target.config.width, target.config.height),
+            #[cfg(test)]
+            Self::Offscreen(target) => (target.width(), target.height()),
+        }
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        match self {
+            Self::Surface(target) => {
+                target.is_configured = true;
+                target.config.width = width.max(1);
+                target.config.height = height.max(1);
+                target.surface.configure(device, &target.config);
+            }
+            #[cfg(test)]
+            Self::Offscreen(target) => {
+                *target = OffscreenTarget::new(device, width, height, target.texture.format());
+            }
+        }
+    }
+
+    pub(crate) fn set_options(&mut self, device: &wgpu::Device, options: SurfaceOptions) {
+        match self {
+            Self::Surface(target) => {
+                target.config.present_mode =
+                    choose_present_mode(&target.present_modes, options.present_mode);
+                target.config.desired_maximum_frame_latency = options.max_frame_latency.max(1);
+                target.surface.configure(device, &target.config);
+            }
+            #[cfg(test)]
+            Self::Offscreen(_) => {}
+        }
+    }
+
+    pub(crate) fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Option<(Frame, wgpu::TextureView)>> {
+        match self {
+            Self::Surface(target) => Ok(target.acquire(device)?.map(|frame| {
+                let view = frame.texture.create_view(&Default::default());
+                (Frame::Surface(frame), view)
+            })),
+            #[cfg(test)]
+            Self::Offscreen(target) => Ok(Some((Frame::Offscreen, target.view.clone()))),
+        }
+    }
+
+    /// Reads back what was drawn into `frame`, before it's presented.
+    pub(crate) fn read_frame(
+        &self,
+        frame: &Frame,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let texture = match (self, frame) {
+            (Self::Surface(target), Frame::Surface(frame)) => {
+                anyhow::ensure!(
+                    target.config.usage.contains(wgpu::TextureUsages::COPY_SRC),
+                    "The surface can't be copied from"
+                );
+                &frame.texture
+            }
+            #[cfg(test)]
+            (Self::Offscreen(target), Frame::Offscreen) => &target.texture,
+            #[cfg(test)]
+            _ => anyhow::bail!("The frame isn't from this target"),
+        };
+        read_texture(texture, device, queue)
+    }
+}
+
+impl Frame {
+    pub(crate) fn present(self) {
+        match self {
+            Self::Surface(frame) => frame.present(),
+            #[cfg(test)]
+            Self::Offscreen => {}
+        }
+    }
+}
+
+/// Falls back to the closest mode the surface supports, ending with Fifo
+/// which is always supported.
+fn choose_present_mode(supported: &[wgpu::PresentMode], mode: PresentMode) -> wgpu::PresentMode {
+    use wgpu::PresentMode as Wgpu;
+
+    let preferred: &[Wgpu] = match mode {
+        PresentMode::Fifo => &[Wgpu::Fifo],
+        PresentMode::Mailbox => &[Wgpu::Mailbox, Wgpu::Fifo],
+        PresentMode::Immediate => &[Wgpu::Immediate, Wgpu::Mailbox, Wgpu::Fifo],
+    };
+    let chosen = preferred
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(Wgpu::Fifo);
+    if chosen != preferred[0] {
+        log::warn!("{mode:?} isn't supported, presenting with {chosen:?}");
+    }
+    chosen
+}
+
+/// Copies `texture` back from the GPU, blocking until it's done.
+fn read_texture(
+    texture: &wgpu::Texture,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<image::RgbaImage> {
+    let format = texture.format();
+    let swizzle = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => anyhow::bail!("Unable to read back {format:?} textures"),
+    };
+
+    let (width, height) = (texture.width(), texture.height());
+    let row_bytes = width * 4;
+    // Rows in buffer copies have to be aligned
+    let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("offscreen_readback"),
+        size: (padded_row_bytes * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    receiver.recv()??;
+
+    let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks_exact(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if swizzle {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    image::RgbaImage::from_raw(width, height, pixels).context("Readback buffer is the wrong size")
+}
@@ -0,0 +1,518 @@
+//! Retained text geometry for UI text that's queued every frame.
+//!
+//! Laying out text and generating glyph quads only happens the first frame a
+//! string is seen. Text is laid out at the origin and moved into place when
+//! it's packed, so moving text doesn't lay it out again. Everything queued in
+//! a frame is packed into one vertex and index buffer per font, which is only
+//! reuploaded when the set of strings, their order or their positions change.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+};
+
+use crate::render::{
+    bindings::CameraBinding,
+    buffer::BackedBuffer,
+    font::{FontId, FontRegistry, TextPipeline, TextStyle, TextStyleBinding, generate_text_data},
+    rich_text::TextSpan,
+    text_layout::TextLayout,
+    utils::Rect,
+    vertex::TextVertex,
+};
+
+struct CachedText {
+    font: FontId,
+    text: Vec<TextSpan>,
+    /// Layout with its bounds moved to the origin
+    layout: TextLayout,
+    vertices: Vec<TextVertex>,
+    indices: Vec<u32>,
+    bounds: Rect,
+    last_used: u64,
+}
+
+impl CachedText {
+    fn matches(&self, font: FontId, text: &[TextSpan], layout: &TextLayout) -> bool {
+        self.font == font && self.text == text && self.layout == *layout
+    }
+}
+
+/// A string queued this frame.
+#[derive(Clone, Copy, PartialEq)]
+struct QueuedText {
+    key: u64,
+    /// Index into [`TextCache::styles`]
+    style: usize,
+    /// Added to the cached geometry, which is laid out at the origin
+    offset: glam::Vec2,
+}
+
+/// One draw call worth of text sharing a style.
+struct DrawRange {
+    style: usize,
+    indices: Range<u32>,
+}
+
+struct FontBatch {
+    font: FontId,
+    /// Text this batch was built from, to tell when it changed
+    contents: Vec<QueuedText>,
+    vertices: BackedBuffer<TextVertex>,
+    indices: BackedBuffer<u32>,
+    ranges: Vec<DrawRange>,
+}
+
+/// Work done by a [`TextCache`] since the stats were last taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextCacheStats {
+    /// Strings that had to be laid out
+    pub layouts: u32,
+    /// Font batches that were rebuilt and written to the GPU
+    pub uploads: u32,
+    pub uploaded_bytes: u64,
+}
+
+pub struct TextCache {
+    entries: HashMap<u64, CachedText>,
+    /// Strings queued this frame, in order
+    queued: Vec<QueuedText>,
+    /// Styles of the text queued this frame, or last frame until it's
+    /// prepared
+    styles: Vec<TextStyleBinding>,
+    batches: Vec<FontBatch>,
+    frame: u64,
+    rebuild: bool,
+    stats: TextCacheStats,
+}
+
+impl TextCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            queued: Vec::new(),
+            styles: Vec::new(),
+            batches: Vec::new(),
+            frame: 0,
+            rebuild: false,
+            stats: TextCacheStats::default(),
+        }
+    }
+
+    /// Adds text to draw this frame, returning the space it takes up.
+    #[allow(clippy::too_many_arguments)]
+    pub fn queue(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline: &TextPipeline,
+        fonts: &FontRegistry,
+        font: FontId,
+        text: &[TextSpan],
+        layout: &TextLayout,
+        style: TextStyle,
+    ) -> Rect {
+        let offset = layout.bounds.min;
+        let layout = &TextLayout {
+            bounds: layout.bounds.translate(-offset),
+            ..*layout
+        };
+        let key = text_key(font, text, layout);
+
+        let entry = match self.entries.get_mut(&key) {
+            Some(entry) if entry.matches(font, text, layout) => entry,
+            _ => {
+                let (vertices, indices, bounds) = generate_text_data(fonts.get(font), text, layout);
+                self.stats.layouts += 1;
+                // Only a hash collision replaces an entry, and batches can't
+                // tell its geometry changed
+                self.rebuild |= self
+                    .entries
+                    .insert(
+                        key,
+                        CachedText {
+                            font,
+                            text: text.to_vec(),
+                            layout: *layout,
+                            vertices,
+                            indices,
+                            bounds,
+                            last_used: self.frame,
+                        },
+                    )
+                    .is_some();
+                self.entries.get_mut(&key).unwrap()
+            }
+        };
+        entry.last_used = self.frame;
+        let bounds = entry.bounds.translate(offset);
+
+        let style = match self.styles.iter().position(|s| *s.style() == style) {
+            Some(i) => i,
+            None => {
+                self.styles.push(pipeline.bind_style(device, style));
+                self.styles.len() - 1
+            }
+        };
+
+        self.queued.push(QueuedText { key, style, offset });
+        bounds
+    }
+
+    /// Uploads the batches of any fonts whose text changed since the last
+    /// frame and drops text and styles that weren't queued this frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.prune_styles();
+
+        // Fonts are drawn in the order they were first queued
+        let mut fonts = Vec::new();
+        for text in &self.queued {
+            let font = self.entries[&text.key].font;
+            if !fonts.contains(&font) {
+                fonts.push(font);
+            }
+        }
+
+        let mut old_batches = std::mem::take(&mut self.batches);
+        for font in fonts {
+            let contents = self
+                .queued
+                .iter()
+                .copied()
+                .filter(|text| self.entries[&text.key].font == font)
+                .collect::<Vec<_>>();
+
+            let mut batch = match old_batches.iter().position(|b| b.font == font) {
+                Some(i) => old_batches.swap_remove(i),
+                None => FontBatch {
+                    font,
+                    contents: Vec::new(),
+                    vertices: BackedBuffer::with_capacity(device, 256, wgpu::BufferUsages::VERTEX),
+                    indices: BackedBuffer::with_capacity(device, 384, wgpu::BufferUsages::INDEX),
+                    ranges: Vec::new(),
+                },
+            };
+
+            if batch.contents == contents && !self.rebuild {
+                self.batches.push(batch);
+                continue;
+            }
+
+            batch.vertices.clear();
+            batch.indices.clear();
+            batch.ranges.clear();
+            {
+                let mut vertices = batch.vertices.batch(device, queue);
+                let mut indices = batch.indices.batch(device, queue);
+                let mut base_vertex = 0;
+                let mut index_count = 0;
+
+                for text in &contents {
+                    let entry = &self.entries[&text.key];
+                    for &v in &entry.vertices {
+                        vertices.push(TextVertex {
+                            position: v.position + text.offset,
+                            ..v
+                        });
+                    }
+                    for &i in &entry.indices {
+                        indices.push(base_vertex + i);
+                    }
+
+                    let start = index_count;
+                    index_count += entry.indices.len() as u32;
+                    base_vertex += entry.vertices.len() as u32;
+
+                    match batch.ranges.last_mut() {
+                        Some(range) if range.style == text.style => range.indices.end = index_count,
+                        _ => batch.ranges.push(DrawRange {
+                            style: text.style,
+                            indices: start..index_count,
+                        }),
+                    }
+                }
+            }
+
+            self.stats.uploads += 1;
+            self.stats.uploaded_bytes += (batch.vertices.len() as usize * size_of::<TextVertex>()
+                + batch.indices.len() as usize * size_of::<u32>())
+                as u64;
+            batch.contents = contents;
+            self.batches.push(batch);
+        }
+        self.rebuild = false;
+
+        let frame = self.frame;
+        self.entries.retain(|_, entry| entry.last_used == frame);
+    }
+
+    /// Drops the styles nothing was queued with this frame. Queued text is
+    /// remapped to the new indices, batches get rebuilt by their contents no
+    /// longer matching.
+    fn prune_styles(&mut self) {
+        let mut used = vec![false; self.styles.len()];
+        for text in &self.queued {
+            used[text.style] = true;
+        }
+        if used.iter().all(|&used| used) {
+            return;
+        }
+
+        let mut remap = Vec::with_capacity(used.len());
+        let mut next = 0;
+        for &used in &used {
+            remap.push(next);
+            next += used as usize;
+        }
+        let mut used = used.into_iter();
+        self.styles.retain(|_| used.next().unwrap());
+        for text in &mut self.queued {
+            text.style = remap[text.style];
+        }
+    }
+
+    /// Draws everything queued this frame, then starts a new frame.
+    pub fn draw(
+        &mut self,
+        pass: &mut wgpu::RenderPass<'_>,
+        pipeline: &TextPipeline,
+        fonts: &FontRegistry,
+        camera_binding: &CameraBinding,
+    ) {
+        for batch in &self.batches {
+            if batch.ranges.is_empty() {
+                continue;
+            }
+            pipeline.bind(pass, fonts, batch.font, camera_binding);
+            pass.set_vertex_buffer(0, batch.vertices.buffer().slice(..));
+            pass.set_index_buffer(batch.indices.buffer().slice(..), wgpu::IndexFormat::Uint32);
+            for range in &batch.ranges {
+                let style = &self.styles[range.style];
+                pass.set_bind_group(2, style.bind_group(), &[]);
+                pass.draw_indexed(range.indices.clone(), 0, style.instances());
+            }
+        }
+
+        self.queued.clear();
+        self.frame += 1;
+    }
+
+    /// Returns and resets the counters. Static text should stop showing up
+    /// in them after its first frame.
+    pub fn take_stats(&mut self) -> TextCacheStats {
+        std::mem::take(&mut self.stats)
+    }
+}
+
+fn text_key(font: FontId, text: &[TextSpan], layout: &TextLayout) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    font.hash(&mut hasher);
+    for span in text {
+        span.text.hash(&mut hasher);
+        span.style
+            .color
+            .to_array()
+            .map(f32::to_bits)
+            .hash(&mut hasher);
+        span.style.size.map(f32::to_bits).hash(&mut hasher);
+        span.style.italic.hash(&mut hasher);
+    }
+    // Only the size, text is laid out at the origin
+    layout
+        .bounds
+        .size()
+        .to_array()
+        .map(f32::to_bits)
+        .hash(&mut hasher);
+    layout.h_align.hash(&mut hasher);
+    layout.v_align.hash(&mut hasher);
+    layout.max_width.map(f32::to_bits).hash(&mut hasher);
+    layout.line_spacing.to_bits().hash(&mut hasher);
+    layout.kerning.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{
+        assets::AssetManager, bindings::CameraBinder, font::Font, resources::FsResources,
+        rich_text::SpanStyle, uniform::CameraData,
+    };
+
+    struct Harness {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        pipeline: TextPipeline,
+        fonts: FontRegistry,
+        font: FontId,
+        camera_binding: CameraBinding,
+        target: wgpu::TextureView,
+        cache: TextCache,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let (device, queue) = crate::render::test_device();
+            let assets = AssetManager::new(
+                FsResources::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res")),
+                &device,
+                &queue,
+            );
+            let camera_binder = CameraBinder::new(&device);
+            let pipeline =
+                TextPipeline::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb, &camera_binder)
+                    .unwrap();
+            let mut fonts = FontRegistry::new();
+            let font = fonts
+                .register(
+                    &device,
+                    &pipeline,
+                    "ui",
+                    assets
+                        .load_blocking::<Font>("fonts/OpenSans MSDF.zip")
+                        .unwrap(),
+                )
+                .unwrap();
+            let camera = BackedBuffer::with_data(
+                &device,
+                vec![CameraData::IDENTITY],
+                wgpu::BufferUsages::UNIFORM,
+            );
+            let camera_binding = camera_binder.bind(&device, &camera);
+            let target = device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: None,
+                    size: wgpu::Extent3d {
+                        width: 64,
+                        height: 64,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default());
+            Self {
+                device,
+                queue,
+                pipeline,
+                fonts,
+                font,
+                camera_binding,
+                target,
+                cache: TextCache::new(),
+            }
+        }
+
+        /// Queues, prepares and draws a frame of `texts`, returning the
+        /// cache's stats for it.
+        fn frame(&mut self, texts: &[(&str, TextLayout, TextStyle)]) -> TextCacheStats {
+            for (text, layout, style) in texts {
+                self.cache.queue(
+                    &self.device,
+                    &self.pipeline,
+                    &self.fonts,
+                    self.font,
+                    &[TextSpan::new(*text, SpanStyle::default())],
+                    layout,
+                    *style,
+                );
+            }
+            self.cache.prepare(&self.device, &self.queue);
+
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &self.target,
+                        resolve_target: None,
+                        ops: Default::default(),
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                self.cache
+                    .draw(&mut pass, &self.pipeline, &self.fonts, &self.camera_binding);
+            }
+            self.queue.submit([encoder.finish()]);
+            self.cache.take_stats()
+        }
+    }
+
+    fn at(x: f32, y: f32) -> TextLayout {
+        TextLayout::at(glam::vec2(x, y))
+    }
+
+    #[test]
+    fn unchanged_frames_dont_upload() {
+        let mut harness = Harness::new();
+        let style = TextStyle::default();
+        let texts = [
+            ("hello", at(0.0, 0.0), style),
+            ("world", at(0.0, 20.0), style),
+        ];
+
+        let first = harness.frame(&texts);
+        assert_eq!(first.layouts, 2);
+        assert_eq!(first.uploads, 1);
+
+        for _ in 0..3 {
+            assert_eq!(harness.frame(&texts), TextCacheStats::default());
+        }
+    }
+
+    #[test]
+    fn moving_text_doesnt_lay_it_out_again() {
+        let mut harness = Harness::new();
+        let style = TextStyle::default();
+        harness.frame(&[("label", at(0.0, 0.0), style)]);
+
+        for i in 1..4 {
+            let stats = harness.frame(&[("label", at(i as f32 * 10.0, 5.0), style)]);
+            assert_eq!(stats.layouts, 0);
+            assert_eq!(stats.uploads, 1);
+        }
+    }
+
+    #[test]
+    fn queue_returns_bounds_at_the_text_position() {
+        let mut harness = Harness::new();
+        let style = TextStyle::default();
+        let mut bounds = |layout: &TextLayout| {
+            harness.cache.queue(
+                &harness.device,
+                &harness.pipeline,
+                &harness.fonts,
+                harness.font,
+                &[TextSpan::new("text", SpanStyle::default())],
+                layout,
+                style,
+            )
+        };
+        let origin = bounds(&at(0.0, 0.0));
+        let moved = bounds(&at(30.0, 40.0));
+        assert_eq!(moved, origin.translate(glam::vec2(30.0, 40.0)));
+    }
+
+    #[test]
+    fn unused_styles_are_dropped() {
+        let mut harness = Harness::new();
+        for i in 0..10 {
+            let style = TextStyle::default().with_outline(i as f32, glam::Vec4::ONE);
+            harness.frame(&[
+                ("fixed", at(0.0, 0.0), TextStyle::default()),
+                ("animated", at(0.0, 20.0), style),
+            ]);
+        }
+        assert_eq!(harness.cache.styles.len(), 2);
+
+        harness.frame(&[("fixed", at(0.0, 0.0), TextStyle::default())]);
+        assert_eq!(harness.cache.styles.len(), 1);
+    }
+}
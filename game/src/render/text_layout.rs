@@ -0,0 +1,441 @@
+use crate::render::{font::Font, rich_text::TextSpan, utils::Rect};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+}
+
+/// Where and how to place text. Coordinates are in UI space, y down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLayout {
+    /// Text is aligned within this rect. A zero sized rect works as an anchor
+    /// point, eg. centered text is centered on it.
+    pub bounds: Rect,
+    pub h_align: HAlign,
+    pub v_align: VAlign,
+    /// Lines longer than this are wrapped at spaces, or between characters
+    /// when a single word doesn't fit.
+    pub max_width: Option<f32>,
+    /// Multiplier on the font's line height
+    pub line_spacing: f32,
+    pub kerning: bool,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self {
+            bounds: Rect::ZERO,
+            h_align: HAlign::Left,
+            v_align: VAlign::Top,
+            max_width: None,
+            line_spacing: 1.0,
+            kerning: true,
+        }
+    }
+}
+
+impl TextLayout {
+    pub fn at(position: glam::Vec2) -> Self {
+        Self {
+            bounds: Rect::new(position, position),
+            ..Default::default()
+        }
+    }
+
+    pub fn in_rect(bounds: Rect) -> Self {
+        Self {
+            bounds,
+            ..Default::default()
+        }
+    }
+
+    pub fn align(mut self, h_align: HAlign, v_align: VAlign) -> Self {
+        self.h_align = h_align;
+        self.v_align = v_align;
+        self
+    }
+}
+
+/// A glyph positioned by [`layout_text`].
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedGlyph {
+    /// Index into [`crate::render::font::FontData::glyphs`]
+    pub glyph: usize,
+    /// Index of the span the glyph came from
+    pub span: usize,
+    pub rect: Rect,
+}
+
+#[derive(Debug, Clone)]
+pub struct LaidOutText {
+    pub glyphs: Vec<PlacedGlyph>,
+    /// Space taken up by the lines of text, which can be smaller than the
+    /// glyphs' rects as it's based on the line height.
+    pub bounds: Rect,
+}
+
+struct Item {
+    c: char,
+    glyph: usize,
+    span: usize,
+    scale: f32,
+}
+
+#[derive(Default)]
+struct Line {
+    /// Item index and x offset from the start of the line
+    items: Vec<(usize, f32)>,
+    width: f32,
+    /// Scale of the line's tallest span
+    scale: f32,
+}
+
+struct LineBuilder<'a> {
+    font: &'a Font,
+    items: &'a [Item],
+    kerning: bool,
+    lines: Vec<Line>,
+    line: Line,
+    x: f32,
+}
+
+impl LineBuilder<'_> {
+    fn advance(&self, i: usize) -> f32 {
+        let item = &self.items[i];
+        let mut advance = self.font.info.glyphs[item.glyph].xadvance as f32 * item.scale;
+        if let Some(next) = self.items.get(i + 1).filter(|_| self.kerning) {
+            advance += self.font.kerning(item.c, next.c) * item.scale;
+        }
+        advance
+    }
+
+    fn width(&self, range: std::ops::Range<usize>) -> f32 {
+        range.map(|i| self.advance(i)).sum()
+    }
+
+    fn push(&mut self, i: usize) {
+        self.line.items.push((i, self.x));
+        self.line.scale = self.line.scale.max(self.items[i].scale);
+        self.x += self.advance(i);
+    }
+
+    fn finish_line(&mut self, scale: f32) {
+        let mut line = std::mem::take(&mut self.line);
+        // Trailing spaces don't count towards the width, so aligned text
+        // lines up with the last visible glyph
+        line.width = line
+            .items
+            .iter()
+            .rev()
+            .find(|(i, _)| !self.items[*i].c.is_whitespace())
+            .map(|&(i, x)| x + self.advance(i))
+            .unwrap_or(0.0);
+        if line.scale == 0.0 {
+            line.scale = scale;
+        }
+        self.lines.push(line);
+        self.x = 0.0;
+    }
+}
+
+pub fn layout_text(font: &Font, text: &[TextSpan], layout: &TextLayout) -> LaidOutText {
+    let native_size = font.info.info.size as f32;
+    let unknown = font.glyph_index(font.unknown_char()).unwrap_or(0);
+
+    let mut items = Vec::new();
+    for (span, s) in text.iter().enumerate() {
+        let scale = s.style.size.map(|size| size / native_size).unwrap_or(1.0);
+        for c in s.text.chars() {
+            items.push(Item {
+                c,
+                glyph: font.glyph_index(c).unwrap_or(unknown),
+                span,
+                scale,
+            });
+        }
+    }
+
+    let mut builder = LineBuilder {
+        font,
+        items: &items,
+        kerning: layout.kerning,
+        lines: Vec::new(),
+        line: Line::default(),
+        x: 0.0,
+    };
+    let default_scale = text
+        .first()
+        .and_then(|s| s.style.size)
+        .map(|size| size / native_size)
+        .unwrap_or(1.0);
+
+    let mut i = 0;
+    while i < items.len() {
+        let c = items[i].c;
+
+        if c == '\n' {
+            builder.finish_line(items[i].scale);
+            i += 1;
+            continue;
+        }
+
+        if c.is_whitespace() {
+            builder.push(i);
+            i += 1;
+            continue;
+        }
+
+        // Place the whole word, wrapping first if it doesn't fit
+        let end = (i..items.len())
+            .find(|&j| items[j].c.is_whitespace())
+            .unwrap_or(items.len());
+        let word_width = builder.width(i..end);
+
+        if let Some(max_width) = layout.max_width {
+            let has_glyphs = builder
+                .line
+                .items
+                .iter()
+                .any(|(j, _)| !items[*j].c.is_whitespace());
+            if has_glyphs && builder.x + word_width > max_width {
+                // Spaces at the wrap point are dropped
+                while builder
+                    .line
+                    .items
+                    .last()
+                    .is_some_and(|(j, _)| items[*j].c.is_whitespace())
+                {
+                    builder.line.items.pop();
+                }
+                builder.finish_line(default_scale);
+            }
+
+            if word_width > max_width {
+                for j in i..end {
+                    if !builder.line.items.is_empty() && builder.x + builder.advance(j) > max_width
+                    {
+                        builder.finish_line(default_scale);
+                    }
+                    builder.push(j);
+                }
+                i = end;
+                continue;
+            }
+        }
+
+        for j in i..end {
+            builder.push(j);
+        }
+        i = end;
+    }
+
+    // A trailing newline doesn't add an empty line
+    if !builder.line.items.is_empty() || builder.lines.is_empty() {
+        builder.finish_line(default_scale);
+    }
+
+    let lines = builder.lines;
+
+    let line_height = font.info.common.line_height as f32 * layout.line_spacing;
+    let base = font.info.common.base as f32;
+    let total_height = lines.iter().map(|l| l.scale * line_height).sum::<f32>();
+
+    let bounds = layout.bounds;
+    let mut y = match layout.v_align {
+        VAlign::Top => bounds.min.y,
+        VAlign::Middle => bounds.center().y - total_height * 0.5,
+    };
+
+    let mut glyphs = Vec::with_capacity(items.len());
+    let mut text_bounds: Option<Rect> = None;
+
+    for line in &lines {
+        let x0 = match layout.h_align {
+            HAlign::Left => bounds.min.x,
+            HAlign::Center => bounds.center().x - line.width * 0.5,
+            HAlign::Right => bounds.max.x - line.width,
+        };
+        let height = line.scale * line_height;
+        let line_base = line.scale * base;
+
+        for &(i, x) in &line.items {
+            let item = &items[i];
+            let glyph = &font.info.glyphs[item.glyph];
+            if glyph.width == 0 || glyph.height == 0 {
+                continue;
+            }
+            // Line up the baselines of differently sized spans
+            let top = y + line_base - base * item.scale;
+            let min = glam::vec2(
+                x0 + x + glyph.xoffset as f32 * item.scale,
+                top + glyph.yoffset as f32 * item.scale,
+            );
+            let size = glam::vec2(glyph.width as f32, glyph.height as f32) * item.scale;
+            glyphs.push(PlacedGlyph {
+                glyph: item.glyph,
+                span: item.span,
+                rect: Rect::from_pos_size(min, size),
+            });
+        }
+
+        let line_rect = Rect::new(glam::vec2(x0, y), glam::vec2(x0 + line.width, y + height));
+        text_bounds = Some(match text_bounds {
+            Some(b) => b.union(&line_rect),
+            None => line_rect,
+        });
+
+        y += height;
+    }
+
+    LaidOutText {
+        glyphs,
+        bounds: text_bounds.unwrap_or(Rect::new(bounds.min, bounds.min)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::rich_text::SpanStyle;
+
+    fn font() -> Font {
+        let (device, queue) = crate::render::test_device();
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/res/fonts/OpenSans MSDF.zip"
+        ))
+        .unwrap();
+        Font::from_bytes(bytes, Font::UNKNOWN_CHAR, &device, &queue).unwrap()
+    }
+
+    fn layout(font: &Font, text: &str, layout: TextLayout) -> LaidOutText {
+        layout_text(font, &[TextSpan::new(text, SpanStyle::default())], &layout)
+    }
+
+    fn advance(font: &Font, c: char) -> f32 {
+        font.info.glyphs[font.glyph_index(c).unwrap()].xadvance as f32
+    }
+
+    fn lines(font: &Font, text: &LaidOutText) -> f32 {
+        text.bounds.height() / font.info.common.line_height as f32
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        let font = font();
+        let two_words = layout(&font, "aaa bbb", TextLayout::default())
+            .bounds
+            .width();
+        let text = layout(
+            &font,
+            "aaa bbb ccc ddd",
+            TextLayout {
+                max_width: Some(two_words + 1.0),
+                ..Default::default()
+            },
+        );
+        assert_eq!(lines(&font, &text), 2.0);
+        assert_eq!(text.bounds.width(), two_words);
+        assert_eq!(text.glyphs.len(), 12);
+
+        let text = layout(&font, "aaa bbb ccc ddd", TextLayout::default());
+        assert_eq!(lines(&font, &text), 1.0);
+    }
+
+    #[test]
+    fn long_words_break_between_characters() {
+        let font = font();
+        let max_width = advance(&font, 'a') * 3.0;
+        let text = layout(
+            &font,
+            "aaaaaaaaaa",
+            TextLayout {
+                max_width: Some(max_width),
+                ..Default::default()
+            },
+        );
+        assert_eq!(lines(&font, &text), 4.0);
+        assert!(text.bounds.width() <= max_width);
+    }
+
+    #[test]
+    fn newlines_start_new_lines() {
+        let font = font();
+        assert_eq!(
+            lines(&font, &layout(&font, "a\nb", TextLayout::default())),
+            2.0
+        );
+        assert_eq!(
+            lines(&font, &layout(&font, "a\nb\n", TextLayout::default())),
+            2.0
+        );
+        assert_eq!(
+            lines(&font, &layout(&font, "a\n\nb", TextLayout::default())),
+            3.0
+        );
+    }
+
+    #[test]
+    fn aligns_within_bounds() {
+        let font = font();
+        let rect = Rect::new(glam::vec2(10.0, 20.0), glam::vec2(410.0, 320.0));
+        let text = "one\nthree";
+
+        let top_left = layout(&font, text, TextLayout::in_rect(rect));
+        assert_eq!(top_left.bounds.min, rect.min);
+
+        let centered = layout(
+            &font,
+            text,
+            TextLayout::in_rect(rect).align(HAlign::Center, VAlign::Middle),
+        );
+        assert!(centered.bounds.center().abs_diff_eq(rect.center(), 1e-3));
+
+        let top_right = layout(
+            &font,
+            text,
+            TextLayout::in_rect(rect).align(HAlign::Right, VAlign::Top),
+        );
+        assert!((top_right.bounds.max.x - rect.max.x).abs() < 1e-3);
+        assert_eq!(top_right.bounds.min.y, rect.min.y);
+        // The shorter first line is pushed right too
+        let first = top_right.glyphs[0].rect.min.x;
+        assert!(first > top_right.bounds.min.x);
+    }
+
+    #[test]
+    fn kerning_adjusts_advances() {
+        let mut font = font();
+        font.kerning_map.insert(('A', 'V'), -5);
+
+        let second_x = |kerning, size| {
+            let spans = [TextSpan::new("AV", SpanStyle::default().with_size(size))];
+            let layout = TextLayout {
+                kerning,
+                ..Default::default()
+            };
+            let text = layout_text(&font, &spans, &layout);
+            text.glyphs[1].rect.min.x
+        };
+        let native_size = font.info.info.size as f32;
+        assert_eq!(
+            second_x(false, native_size) - second_x(true, native_size),
+            5.0
+        );
+        // Kerning scales with the span
+        assert_eq!(
+            second_x(false, native_size * 2.0) - second_x(true, native_size * 2.0),
+            10.0
+        );
+    }
+}
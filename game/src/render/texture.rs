@@ -0,0 +1,169 @@
+use crate::render::assets::{Asset, LoadContext};
+
+pub struct Texture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl Texture {
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::RgbaImage,
+        label: Option<&str>,
+    ) -> Self {
+        Self::from_rgba(device, queue, img.width(), img.height(), img, label)
+    }
+
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        label: Option<&str>,
+    ) -> Self {
+        let texture_size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            texture_size,
+        );
+
+        let view = texture.create_view(&Default::default());
+
+        Self { texture, view }
+    }
+
+    /// Uploads equally sized images as the layers of a 2D array texture, for
+    /// example the pages of a font.
+    pub fn array_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[image::RgbaImage],
+        label: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let Some(first) = images.first() else {
+            anyhow::bail!("Texture array needs at least one image");
+        };
+        let (width, height) = first.dimensions();
+        if images.iter().any(|img| img.dimensions() != (width, height)) {
+            anyhow::bail!("Texture array images must all be {width}x{height}");
+        }
+
+        let layer_size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                // GL guesses the view dimension from the layer count, and
+                // would make a single layer a plain 2D texture
+                depth_or_array_layers: (images.len() as u32).max(2),
+                ..layer_size
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label,
+            view_formats: &[],
+        });
+
+        for (layer, img) in images.iter().enumerate() {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                img,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                layer_size,
+            );
+        }
+
+        // Always an array view, even with a single layer, so it matches the
+        // bind group layout
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        Ok(Self { texture, view })
+    }
+
+    #[cfg(test)]
+    pub fn layers(&self) -> u32 {
+        self.texture.depth_or_array_layers()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
+
+impl Asset for Texture {
+    fn load(ctx: &LoadContext<'_>, bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let (device, queue) = ctx.gpu()?;
+        let img = image::load_from_memory(&bytes)?.to_rgba8();
+        Ok(Self::from_image(
+            device,
+            queue,
+            &img,
+            Some(&ctx.path.display().to_string()),
+        ))
+    }
+}
+
+/// Images are kept on the CPU so they can be processed before upload, for
+/// example packed into an atlas.
+impl Asset for image::RgbaImage {
+    fn load(_ctx: &LoadContext<'_>, bytes: Vec<u8>) -> anyhow::Result<Self> {
+        Ok(image::load_from_memory(&bytes)?.to_rgba8())
+    }
+}
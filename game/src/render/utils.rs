@@ -30,62 +30,42 @@ impl<'a> RenderPipelineBuilder<'a> {
         }
     }
 
-    #[allow(unused)]
     pub fn label(mut self, value: &'a str) -> Self {
         self.label = Some(value);
         self
     }
 
-    #[allow(unused)]
     pub fn layout(mut self, layout: &'a wgpu::PipelineLayout) -> Self {
         self.layout = Some(layout);
         self
     }
 
-    #[allow(unused)]
     pub fn vertex(mut self, state: VertexState<'a>) -> Self {
         self.vertex = Some(state);
         self
     }
 
-    #[allow(unused)]
     pub fn fragment(mut self, state: FragmentState<'a>) -> Self {
         self.fragment = Some(state);
         self
     }
 
-    #[allow(unused)]
-    pub fn depth(
-        mut self,
-        format: wgpu::TextureFormat,
-        depth_compare: wgpu::CompareFunction,
-    ) -> Self {
-        if let Some(state) = &mut self.depth_stencil {
-            state.format = format;
-        } else {
-            self.depth_stencil = Some(wgpu::DepthStencilState {
-                format,
-                depth_write_enabled: true,
-                depth_compare,
-                stencil: Default::default(),
-                bias: Default::default(),
-            })
-        }
-        self
-    }
-
-    #[allow(unused)]
     pub fn topology(mut self, value: wgpu::PrimitiveTopology) -> Self {
         self.primitive.topology = value;
         self
     }
 
-    #[allow(unused)]
     pub fn cull_mode(mut self, value: Option<wgpu::Face>) -> Self {
         self.primitive.cull_mode = value;
         self
     }
 
+    /// Samples per pixel, which has to match the pass's color attachments.
+    pub fn multisample(mut self, count: u32) -> Self {
+        self.multisample.count = count;
+        self
+    }
+
     pub fn build(self, device: &wgpu::Device) -> anyhow::Result<wgpu::RenderPipeline> {
         Ok(
             device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -103,7 +83,70 @@ impl<'a> RenderPipelineBuilder<'a> {
     }
 }
 
-#[allow(unused)]
-pub fn rev_lerp(a: f32, b: f32, c: f32) -> f32 {
-    (c - a) / (b - a)
-}
\ No newline at end of file
+/// Axis aligned rectangle. Used in UI space where y points down, so `min` is
+/// the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub min: glam::Vec2,
+    pub max: glam::Vec2,
+}
+
+impl Rect {
+    pub const ZERO: Self = Self {
+        min: glam::Vec2::ZERO,
+        max: glam::Vec2::ZERO,
+    };
+
+    pub fn new(min: glam::Vec2, max: glam::Vec2) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_pos_size(position: glam::Vec2, size: glam::Vec2) -> Self {
+        Self {
+            min: position,
+            max: position + size,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max.y - self.min.y
+    }
+
+    pub fn size(&self) -> glam::Vec2 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> glam::Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn contains(&self, point: glam::Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmplt(self.max).all()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn translate(&self, offset: glam::Vec2) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Shrinks the rect by `amount` on every side.
+    pub fn inset(&self, amount: f32) -> Self {
+        Self {
+            min: self.min + amount,
+            max: self.max - amount,
+        }
+    }
+}
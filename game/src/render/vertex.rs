@@ -24,18 +24,70 @@ impl Vertex2d {
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
-pub struct InstanceColor2d {
+pub struct InstanceSprite2d {
     position: glam::Vec2,
-    color: [f32;4], // glam::Vec4 is not 4 f32s
+    scale: glam::Vec2,
+    rotation: f32,
+    layer: f32,
+    uv_rect: [f32; 4], // min uv, max uv
+    color: [f32; 4],
 }
 
-impl InstanceColor2d {
+impl InstanceSprite2d {
     pub const VERTEX_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
         array_stride: std::mem::size_of::<Self>() as _,
         step_mode: wgpu::VertexStepMode::Instance,
         attributes: &wgpu::vertex_attr_array![
             2 => Float32x2,
-            3 => Float32x4,
+            3 => Float32x2,
+            4 => Float32,
+            5 => Float32,
+            6 => Float32x4,
+            7 => Float32x4,
+        ],
+    };
+
+    pub fn new(position: glam::Vec2, scale: glam::Vec2, uv_rect: glam::Vec4) -> Self {
+        Self {
+            position,
+            scale,
+            rotation: 0.0,
+            layer: 0.0,
+            uv_rect: uv_rect.into(),
+            color: [1.0; 4],
+        }
+    }
+
+    pub fn with_layer(mut self, layer: f32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_color(mut self, color: glam::Vec4) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn layer(&self) -> f32 {
+        self.layer
+    }
+}
+
+/// One end of a debug line.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct LineVertex {
+    position: glam::Vec2,
+    color: [f32; 4],
+}
+
+impl LineVertex {
+    pub const VERTEX_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as _,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x4,
         ],
     };
 
@@ -45,4 +97,135 @@ impl InstanceColor2d {
             color: color.into(),
         }
     }
-}
\ No newline at end of file
+}
+
+/// A rounded rectangle drawn from its signed distance field, which covers
+/// circles and capsules too. Edges are anti-aliased at any size.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct InstanceShape2d {
+    position: glam::Vec2,
+    half_size: glam::Vec2,
+    rotation: f32,
+    corner_radius: f32,
+    /// Hollows the shape out to a band this wide, 0 fills it
+    thickness: f32,
+    /// Width of the outline drawn outside the shape's edge
+    outline: f32,
+    color: [f32; 4],
+    outline_color: [f32; 4],
+}
+
+impl InstanceShape2d {
+    pub const VERTEX_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as _,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![
+            2 => Float32x2,
+            3 => Float32x2,
+            4 => Float32,
+            5 => Float32,
+            6 => Float32,
+            7 => Float32,
+            8 => Float32x4,
+            9 => Float32x4,
+        ],
+    };
+
+    pub fn rounded_rect(center: glam::Vec2, size: glam::Vec2, corner_radius: f32) -> Self {
+        let half_size = size.abs() * 0.5;
+        Self {
+            position: center,
+            half_size,
+            rotation: 0.0,
+            corner_radius: corner_radius.clamp(0.0, half_size.min_element()),
+            thickness: 0.0,
+            outline: 0.0,
+            color: [1.0; 4],
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn circle(center: glam::Vec2, radius: f32) -> Self {
+        Self::rounded_rect(center, glam::Vec2::splat(radius * 2.0), radius)
+    }
+
+    /// A circle outline, `thickness` wide inside `radius`.
+    pub fn ring(center: glam::Vec2, radius: f32, thickness: f32) -> Self {
+        Self::circle(center, radius).with_thickness(thickness)
+    }
+
+    /// A line from `a` to `b` with round caps.
+    pub fn capsule(a: glam::Vec2, b: glam::Vec2, radius: f32) -> Self {
+        let along = b - a;
+        let size = glam::vec2(along.length() + radius * 2.0, radius * 2.0);
+        Self::rounded_rect((a + b) * 0.5, size, radius).with_rotation(along.to_angle())
+    }
+
+    /// Counterclockwise, in radians.
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_thickness(mut self, thickness: f32) -> Self {
+        self.thickness = thickness.max(0.0);
+        self
+    }
+
+    pub fn with_color(mut self, color: glam::Vec4) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn with_outline(mut self, width: f32, color: glam::Vec4) -> Self {
+        self.outline = width.max(0.0);
+        self.outline_color = color.into();
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct TextVertex {
+    pub(crate) position: glam::Vec2,
+    pub(crate) uv: glam::Vec2,
+    pub(crate) color: [f32; 4],
+    /// Layer of the font's page texture array
+    pub(crate) page: u32,
+}
+
+impl TextVertex {
+    pub const VERTEX_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as _,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x4,
+            3 => Uint32,
+        ],
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capsules_span_their_ends() {
+        let capsule = InstanceShape2d::capsule(glam::vec2(1.0, 1.0), glam::vec2(1.0, 5.0), 0.5);
+        assert_eq!(capsule.position, glam::vec2(1.0, 3.0));
+        assert_eq!(capsule.half_size, glam::vec2(2.5, 0.5));
+        assert_eq!(capsule.corner_radius, 0.5);
+        assert!((capsule.rotation - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rings_are_hollow_circles() {
+        let ring = InstanceShape2d::ring(glam::Vec2::ZERO, 4.0, 1.0);
+        assert_eq!(ring.half_size, glam::Vec2::splat(4.0));
+        assert_eq!(ring.corner_radius, 4.0);
+        assert_eq!(ring.thickness, 1.0);
+    }
+}
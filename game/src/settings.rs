@@ -0,0 +1,308 @@
+//! User settings, saved as json in the platform's config directory.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use winit::keyboard::KeyCode;
+
+use crate::{
+    game::{
+        camera::{ScaleMode, SplitScreen},
+        particles,
+    },
+    render::{PostSettings, PresentMode, SurfaceOptions},
+};
+
+const FILE_NAME: &str = "settings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum WindowMode {
+    Windowed,
+    /// A window covering the whole monitor
+    Borderless,
+    /// Exclusive fullscreen at `Settings::resolution`
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub const ALL: [Self; 3] = [Self::Windowed, Self::Borderless, Self::Fullscreen];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Windowed => "Windowed",
+            Self::Borderless => "Borderless",
+            Self::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+/// Keys for moving the keyboard player. Menus always use the arrow keys,
+/// WASD, enter and escape so bad bindings can't lock anyone out of them.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub up: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            up: vec![KeyCode::KeyW, KeyCode::ArrowUp],
+            left: vec![KeyCode::KeyA, KeyCode::ArrowLeft],
+            down: vec![KeyCode::KeyS, KeyCode::ArrowDown],
+            right: vec![KeyCode::KeyD, KeyCode::ArrowRight],
+        }
+    }
+}
+
+impl Bindings {
+    pub const NAMES: [&str; 4] = ["Move up", "Move left", "Move down", "Move right"];
+
+    /// Up, left, down and right, in that order.
+    pub fn directions(&self) -> [&Vec<KeyCode>; 4] {
+        [&self.up, &self.left, &self.down, &self.right]
+    }
+
+    pub fn directions_mut(&mut self) -> [&mut Vec<KeyCode>; 4] {
+        [
+            &mut self.up,
+            &mut self.left,
+            &mut self.down,
+            &mut self.right,
+        ]
+    }
+
+    /// Index into [`Bindings::directions`] of the direction bound to `key`.
+    pub fn direction(&self, key: KeyCode) -> Option<usize> {
+        self.directions()
+            .iter()
+            .position(|keys| keys.contains(&key))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window_mode: WindowMode,
+    /// Window size in pixels when windowed, and the video mode to use in
+    /// exclusive fullscreen
+    pub resolution: [u32; 2],
+    pub present_mode: PresentMode,
+    pub max_frame_latency: u32,
+    /// Frames per second to stop at, on top of any limit from vsync
+    pub frame_rate_cap: Option<u32>,
+    /// Samples per pixel, 1 turns multisampling off. Falls back to fewer
+    /// when the GPU doesn't support it.
+    pub msaa_samples: u32,
+    /// From 0 to 1. Nothing plays sound yet
+    pub volume: f32,
+    /// Multiplies the size of all UI, on top of the monitor's scale factor
+    pub ui_scale: f32,
+    /// Whether any player can resume a match, rather than only the one who
+    /// paused it
+    pub anyone_can_resume: bool,
+    pub split_screen: SplitScreen,
+    /// How the world fits windows that aren't 16:9
+    pub scale_mode: ScaleMode,
+    /// Most particles alive at once, 0 turns them off
+    pub particle_budget: usize,
+    pub post_process: PostSettings,
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowMode::Windowed,
+            resolution: [1280, 720],
+            present_mode: PresentMode::Fifo,
+            max_frame_latency: 2,
+            frame_rate_cap: None,
+            msaa_samples: 4,
+            volume: 0.8,
+            ui_scale: 1.0,
+            anyone_can_resume: false,
+            split_screen: SplitScreen::Shared,
+            scale_mode: ScaleMode::Expand,
+            particle_budget: particles::DEFAULT_BUDGET,
+            post_process: PostSettings::default(),
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn surface_options(&self) -> SurfaceOptions {
+        SurfaceOptions {
+            present_mode: self.present_mode,
+            max_frame_latency: self.max_frame_latency,
+        }
+    }
+
+    /// Time between frames with the frame rate cap.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        self.frame_rate_cap
+            .filter(|&fps| fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    /// Loads the saved settings, or the defaults if nothing was saved yet.
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(&settings_path()?)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.save_to(&settings_path()?)
+    }
+
+    /// Fields missing from the file are left at their defaults, and ones
+    /// that aren't settings anymore are ignored.
+    fn load_from(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Invalid settings in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Unable to read {}", path.display())),
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Unable to create {}", dir.display()))?;
+        }
+
+        // Write next to the file first so a crash can't leave half of it
+        let json = serde_json::to_string_pretty(self)?;
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json).with_context(|| format!("Unable to write {}", temp.display()))?;
+        fs::rename(&temp, path).with_context(|| format!("Unable to write {}", path.display()))?;
+        log::info!("Saved settings to {}", path.display());
+        Ok(())
+    }
+}
+
+fn settings_path() -> anyhow::Result<PathBuf> {
+    let dir = config_dir().with_context(|| "Unable to find a config directory")?;
+    Ok(dir.join(env!("CARGO_PKG_NAME")).join(FILE_NAME))
+}
+
+/// `%APPDATA%` on Windows, `~/Library/Application Support` on macOS and
+/// `$XDG_CONFIG_HOME` or `~/.config` elsewhere.
+fn config_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    if cfg!(windows) {
+        return var("APPDATA").map(PathBuf::from);
+    }
+
+    let home = var("HOME").map(PathBuf::from);
+    if cfg!(target_os = "macos") {
+        return home.map(|home| home.join("Library/Application Support"));
+    }
+
+    // Relative paths are invalid according to the XDG spec
+    var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| Path::is_absolute(path))
+        .or_else(|| home.map(|home| home.join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for each test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "{}-{name}-{}",
+                env!("CARGO_PKG_NAME"),
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn saved_settings_load_the_same() {
+        let dir = TempDir::new("round-trip");
+        let path = dir.0.join("nested").join(FILE_NAME);
+        let settings = Settings {
+            window_mode: WindowMode::Borderless,
+            resolution: [1920, 1080],
+            frame_rate_cap: Some(144),
+            msaa_samples: 8,
+            volume: 0.3,
+            ui_scale: 1.5,
+            anyone_can_resume: true,
+            split_screen: SplitScreen::TwoHorizontal,
+            bindings: Bindings {
+                up: vec![KeyCode::KeyI],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::load_from(&path).unwrap(), settings);
+        // The temporary file was moved into place
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn missing_files_load_the_defaults() {
+        let dir = TempDir::new("missing");
+        let settings = Settings::load_from(&dir.0.join(FILE_NAME)).unwrap();
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn missing_and_unknown_fields_are_fine() {
+        let dir = TempDir::new("partial");
+        let path = dir.0.join(FILE_NAME);
+        fs::create_dir_all(&dir.0).unwrap();
+        fs::write(
+            &path,
+            r#"{ "volume": 0.5, "fov": 90, "bindings": { "up": ["KeyI"], "jump": [] } }"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load_from(&path).unwrap();
+        assert_eq!(settings.volume, 0.5);
+        assert_eq!(settings.bindings.up, [KeyCode::KeyI]);
+        assert_eq!(settings.bindings.down, Bindings::default().down);
+        assert_eq!(
+            Settings {
+                volume: 0.8,
+                bindings: Bindings::default(),
+                ..settings
+            },
+            Settings::default()
+        );
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let dir = TempDir::new("invalid");
+        let path = dir.0.join(FILE_NAME);
+        fs::create_dir_all(&dir.0).unwrap();
+        fs::write(&path, r#"{ "ui_scale": "big" }"#).unwrap();
+        assert!(Settings::load_from(&path).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{
+    input::{DeviceId, DeviceInput, InputAction},
+    state::{Context, State, Transition, play::Match, player_name},
+    ui::{Panel, Ui},
+};
+
+/// Players join by moving with the device they want to play with.
+#[derive(Debug, Default)]
+pub struct Lobby {
+    devices: Vec<DeviceId>,
+}
+
+impl State for Lobby {
+    fn input(&mut self, _ctx: &mut Context, input: &DeviceInput) -> Transition {
+        let moved = match input.action {
+            InputAction::MoveX(amount) | InputAction::MoveY(amount) => amount.abs() > 0.5,
+            InputAction::Nav(_) | InputAction::Key(_) => false,
+            InputAction::Disconnected => {
+                self.devices.retain(|&device| device != input.device);
+                false
+            }
+        };
+        if moved && !self.devices.contains(&input.device) {
+            self.devices.push(input.device);
+        }
+        Transition::None
+    }
+
+    fn ui(&mut self, ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+        let devices = &self.devices;
+        Panel::new(560.0).show(ui, |ui| {
+            ui.title("Lobby");
+            if devices.is_empty() {
+                ui.label("Move to join");
+            }
+            for i in 0..devices.len() {
+                ui.label(&format!("{} joined", player_name(i)));
+            }
+            ui.space(ui.theme().spacing);
+            if ui.button("Start") && !devices.is_empty() {
+                return Match::start(ctx, devices.clone());
+            }
+            if ui.button("Back") || ui.back() {
+                return Transition::Pop;
+            }
+            Transition::None
+        })
+    }
+}
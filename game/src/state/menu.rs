@@ -0,0 +1,25 @@
+use crate::{
+    state::{Context, State, Transition, lobby::Lobby, settings::SettingsMenu},
+    ui::{Panel, Ui},
+};
+
+#[derive(Debug, Default)]
+pub struct MainMenu;
+
+impl State for MainMenu {
+    fn ui(&mut self, _ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+        Panel::new(420.0).show(ui, |ui| {
+            ui.title("[color=yellow]Pickups[/color]");
+            if ui.button("Play") {
+                return Transition::Push(Box::new(Lobby::default()));
+            }
+            if ui.button("Settings") {
+                return Transition::Push(Box::new(SettingsMenu::default()));
+            }
+            if ui.button("Quit") {
+                return Transition::Quit;
+            }
+            Transition::None
+        })
+    }
+}
@@ -0,0 +1,532 @@
+//! App flow as a stack of states, eg. a pause menu pushed over a match.
+//!
+//! Only the state on top gets input and updates. States are drawn from the
+//! bottom up, starting at the highest one that isn't an overlay, so a pause
+//! menu shows the match it paused. Nothing here touches the window, the app
+//! turns its events into [`DeviceInput`] and [`NavAction`]s first.
+
+mod lobby;
+mod menu;
+mod pause;
+mod play;
+mod results;
+mod settings;
+
+use std::{sync::Arc, time::Duration};
+
+pub use menu::MainMenu;
+
+use crate::{
+    game::{Game, animation::AnimationLibrary},
+    input::{DeviceId, DeviceInput},
+    render::{PLAYER_COLORS, utils::Rect},
+    settings::Settings,
+    ui::{DrawList, NavAction, Theme, Ui, UiState},
+};
+
+/// Shared by all states.
+pub struct Context {
+    pub animations: Arc<AnimationLibrary>,
+    /// Window size in pixels
+    pub screen_size: glam::UVec2,
+    /// Physical pixels per logical pixel of the window's monitor
+    pub scale_factor: f32,
+    pub settings: Settings,
+    /// Toggled with F3, see [`crate::game::debug`]
+    pub debug_draw: bool,
+}
+
+impl Context {
+    /// Multiplies every UI size, so the UI is the same physical size on high
+    /// DPI monitors.
+    pub fn ui_scale(&self) -> f32 {
+        self.settings.ui_scale * self.scale_factor
+    }
+
+    /// UI space is screen pixels divided by [`Context::ui_scale`], the units
+    /// [`Theme`] sizes and panel widths are given in before scaling.
+    pub fn screen_to_ui(&self, screen: glam::Vec2) -> glam::Vec2 {
+        screen / self.ui_scale()
+    }
+
+    /// The inverse of [`Context::screen_to_ui`].
+    pub fn ui_to_screen(&self, ui: glam::Vec2) -> glam::Vec2 {
+        ui * self.ui_scale()
+    }
+}
+
+/// What the stack should do after a state handled something.
+pub enum Transition {
+    None,
+    Push(Box<dyn State>),
+    Pop,
+    Replace(Box<dyn State>),
+    /// Drops every state and starts over from this one
+    Reset(Box<dyn State>),
+    Quit,
+}
+
+pub trait State {
+    fn input(&mut self, _ctx: &mut Context, _input: &DeviceInput) -> Transition {
+        Transition::None
+    }
+
+    fn update(&mut self, _ctx: &mut Context, _dt: Duration) -> Transition {
+        Transition::None
+    }
+
+    /// The window stopped getting input.
+    fn focus_lost(&mut self, _ctx: &mut Context) -> Transition {
+        Transition::None
+    }
+
+    /// Whether nav actions from `device` reach this state's UI.
+    fn accepts_nav(&self, _ctx: &Context, _device: DeviceId) -> bool {
+        true
+    }
+
+    /// Builds this state's UI. States under the top one get no nav actions
+    /// and their transitions are ignored.
+    fn ui(&mut self, _ctx: &mut Context, _ui: &mut Ui<'_>) -> Transition {
+        Transition::None
+    }
+
+    /// The world to draw behind the UI.
+    fn game(&self) -> Option<&Game> {
+        None
+    }
+
+    /// Whether the state below should still be drawn.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+}
+
+struct Entry {
+    state: Box<dyn State>,
+    ui: UiState,
+}
+
+pub struct StateStack {
+    entries: Vec<Entry>,
+    quit: bool,
+    /// The top state changed since the UI was last built
+    changed: bool,
+}
+
+impl StateStack {
+    pub fn new(initial: Box<dyn State>) -> Self {
+        let mut stack = Self {
+            entries: Vec::new(),
+            quit: false,
+            changed: false,
+        };
+        stack.push(initial);
+        stack
+    }
+
+    /// False once a state quits or the last state is popped.
+    pub fn is_running(&self) -> bool {
+        !self.quit && !self.entries.is_empty()
+    }
+
+    pub fn input(&mut self, ctx: &mut Context, input: &DeviceInput) {
+        if let Some(top) = self.entries.last_mut() {
+            let transition = top.state.input(ctx, input);
+            self.apply(ctx, transition);
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, dt: Duration) {
+        if let Some(top) = self.entries.last_mut() {
+            let transition = top.state.update(ctx, dt);
+            self.apply(ctx, transition);
+        }
+    }
+
+    pub fn focus_lost(&mut self, ctx: &mut Context) {
+        if let Some(top) = self.entries.last_mut() {
+            let transition = top.state.focus_lost(ctx);
+            self.apply(ctx, transition);
+        }
+    }
+
+    /// Builds the UI of every visible state. `actions` only go to the top
+    /// state, and are dropped if it only just got there so the press that
+    /// opened a menu doesn't also act on it.
+    pub fn ui(
+        &mut self,
+        ctx: &mut Context,
+        actions: &[(DeviceId, NavAction)],
+        screen: Rect,
+    ) -> DrawList {
+        let actions = match self.entries.last() {
+            Some(top) if !self.changed => actions
+                .iter()
+                .filter(|(device, _)| top.state.accepts_nav(ctx, *device))
+                .map(|&(_, action)| action)
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.changed = false;
+
+        let theme = Theme::default().scaled(ctx.ui_scale());
+        let mut draw = DrawList::default();
+        let mut transition = Transition::None;
+        let first = self.first_visible();
+        let top = self.entries.len().saturating_sub(1);
+        for (i, entry) in self.entries.iter_mut().enumerate().skip(first) {
+            let actions = if i == top { &actions[..] } else { &[] };
+            let mut ui = Ui::new(&mut entry.ui, actions, screen).with_theme(theme.clone());
+            let result = entry.state.ui(ctx, &mut ui);
+            if i == top {
+                transition = result;
+            }
+            draw.append(ui.finish());
+        }
+        self.apply(ctx, transition);
+        draw
+    }
+
+    /// The world of the highest visible state that has one.
+    pub fn game(&self) -> Option<&Game> {
+        self.entries[self.first_visible()..]
+            .iter()
+            .rev()
+            .find_map(|entry| entry.state.game())
+    }
+
+    pub fn resize(&mut self, ctx: &mut Context, width: u32, height: u32) {
+        ctx.screen_size = glam::uvec2(width, height);
+        for entry in &mut self.entries {
+            entry.state.resize(width, height);
+        }
+    }
+
+    fn first_visible(&self) -> usize {
+        self.entries
+            .iter()
+            .rposition(|entry| !entry.state.is_overlay())
+            .unwrap_or(0)
+    }
+
+    fn push(&mut self, state: Box<dyn State>) {
+        self.entries.push(Entry {
+            state,
+            ui: UiState::default(),
+        });
+        self.changed = true;
+    }
+
+    fn apply(&mut self, ctx: &mut Context, transition: Transition) {
+        let mut state = match transition {
+            Transition::None => return,
+            Transition::Push(state) => state,
+            Transition::Pop => {
+                self.entries.pop();
+                self.changed = true;
+                return;
+            }
+            Transition::Replace(state) => {
+                self.entries.pop();
+                state
+            }
+            Transition::Reset(state) => {
+                self.entries.clear();
+                state
+            }
+            Transition::Quit => {
+                self.quit = true;
+                return;
+            }
+        };
+        state.resize(ctx.screen_size.x, ctx.screen_size.y);
+        self.push(state);
+    }
+}
+
+/// "Player n" as markup in the player's color.
+fn player_name(index: usize) -> String {
+    let color = PLAYER_COLORS[index % PLAYER_COLORS.len()]
+        .to_array()
+        .map(|c| format!("{:02x}", (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        .concat();
+    format!("[color=#{color}]Player {}[/color]", index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        game::camera::{ScaleMode, SplitScreen},
+        input::InputAction,
+        state::play::Match,
+    };
+
+    pub(super) const SCREEN: Rect = Rect {
+        min: glam::Vec2::ZERO,
+        max: glam::vec2(1280.0, 720.0),
+    };
+
+    pub(super) fn context() -> Context {
+        let json =
+            std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/res/animations.json"))
+                .unwrap();
+        Context {
+            animations: Arc::new(AnimationLibrary::from_json(&json).unwrap()),
+            screen_size: glam::uvec2(1280, 720),
+            scale_factor: 1.0,
+            settings: Settings::default(),
+            debug_draw: false,
+        }
+    }
+
+    fn nav(action: NavAction) -> DeviceInput {
+        DeviceInput::new(DeviceId::Keyboard, InputAction::Nav(action))
+    }
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Logs what it's called with and answers input with `script` in order.
+    struct Probe {
+        name: &'static str,
+        log: Log,
+        script: Vec<Transition>,
+        overlay: bool,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, log: &Log, script: Vec<Transition>) -> Box<Self> {
+            Box::new(Self {
+                name,
+                log: log.clone(),
+                script: script.into_iter().rev().collect(),
+                overlay: false,
+            })
+        }
+
+        fn overlay(mut self: Box<Self>) -> Box<Self> {
+            self.overlay = true;
+            self
+        }
+
+        fn record(&self, event: &str) {
+            self.log.borrow_mut().push(format!("{} {event}", self.name));
+        }
+    }
+
+    impl State for Probe {
+        fn input(&mut self, _ctx: &mut Context, _input: &DeviceInput) -> Transition {
+            self.record("input");
+            self.script.pop().unwrap_or(Transition::None)
+        }
+
+        fn update(&mut self, _ctx: &mut Context, _dt: Duration) -> Transition {
+            self.record("update");
+            Transition::None
+        }
+
+        fn ui(&mut self, _ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+            self.record("ui");
+            if ui.back() {
+                return Transition::Pop;
+            }
+            Transition::None
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+
+        fn resize(&mut self, width: u32, height: u32) {
+            self.record(&format!("resize {width}x{height}"));
+        }
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        std::mem::take(&mut *log.borrow_mut())
+    }
+
+    #[test]
+    fn push_replace_and_pop() {
+        let mut ctx = context();
+        let log = Log::default();
+        let b = Probe::new(
+            "b",
+            &log,
+            vec![Transition::Replace(Probe::new(
+                "c",
+                &log,
+                vec![Transition::Pop],
+            ))],
+        );
+        let mut stack = StateStack::new(Probe::new("a", &log, vec![Transition::Push(b)]));
+
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        stack.update(&mut ctx, Duration::ZERO);
+        assert_eq!(stack.entries.len(), 2);
+        assert_eq!(take(&log), ["a input", "b resize 1280x720", "b update"]);
+
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        stack.update(&mut ctx, Duration::ZERO);
+        assert_eq!(stack.entries.len(), 2);
+        assert_eq!(take(&log), ["b input", "c resize 1280x720", "c update"]);
+
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        stack.update(&mut ctx, Duration::ZERO);
+        assert_eq!(stack.entries.len(), 1);
+        assert_eq!(take(&log), ["c input", "a update"]);
+    }
+
+    #[test]
+    fn reset_and_quit() {
+        let mut ctx = context();
+        let log = Log::default();
+        let b = Probe::new(
+            "b",
+            &log,
+            vec![Transition::Reset(Probe::new("c", &log, vec![]))],
+        );
+        let mut stack = StateStack::new(Probe::new("a", &log, vec![Transition::Push(b)]));
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        assert_eq!(stack.entries.len(), 1);
+        assert!(stack.is_running());
+
+        let mut stack = StateStack::new(Probe::new("a", &log, vec![Transition::Quit]));
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        assert!(!stack.is_running());
+
+        let mut stack = StateStack::new(Probe::new("a", &log, vec![Transition::Pop]));
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        assert!(!stack.is_running());
+    }
+
+    #[test]
+    fn overlays_draw_over_the_state_below() {
+        let mut ctx = context();
+        let log = Log::default();
+        let c = Probe::new("c", &log, vec![]);
+        let b = Probe::new("b", &log, vec![Transition::Push(c)]).overlay();
+        let mut stack = StateStack::new(Probe::new("a", &log, vec![Transition::Push(b)]));
+
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        take(&log);
+        stack.ui(&mut ctx, &[], SCREEN);
+        assert_eq!(take(&log), ["a ui", "b ui"]);
+
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+        take(&log);
+        stack.ui(&mut ctx, &[], SCREEN);
+        assert_eq!(take(&log), ["c ui"]);
+    }
+
+    #[test]
+    fn nav_only_reaches_the_top_after_its_first_frame() {
+        let mut ctx = context();
+        let log = Log::default();
+        let b = Probe::new("b", &log, vec![]).overlay();
+        let mut stack = StateStack::new(Probe::new("a", &log, vec![Transition::Push(b)]));
+        stack.ui(&mut ctx, &[], SCREEN);
+        stack.input(&mut ctx, &nav(NavAction::Accept));
+
+        // The press that pushed b doesn't also pop it
+        let back = [(DeviceId::Keyboard, NavAction::Back)];
+        stack.ui(&mut ctx, &back, SCREEN);
+        assert_eq!(stack.entries.len(), 2);
+
+        // Only b gets it, a is drawn below without actions
+        stack.ui(&mut ctx, &back, SCREEN);
+        assert_eq!(stack.entries.len(), 1);
+        assert!(stack.is_running());
+    }
+
+    #[test]
+    fn pause_stops_the_match_under_it() {
+        let mut ctx = context();
+        let mut stack = StateStack::new(Box::new(
+            Match::new(&ctx, vec![DeviceId::Keyboard]).unwrap(),
+        ));
+        stack.update(&mut ctx, Duration::from_millis(1500));
+        let pickups = stack.game().unwrap().pickups().len();
+        assert!(pickups > 0);
+
+        stack.input(&mut ctx, &nav(NavAction::Back));
+        assert_eq!(stack.entries.len(), 2);
+        stack.update(&mut ctx, Duration::from_secs(5));
+        // Still drawn under the pause menu, but frozen
+        assert_eq!(stack.game().unwrap().pickups().len(), pickups);
+
+        stack.entries.pop();
+        stack.update(&mut ctx, Duration::from_secs(5));
+        assert!(stack.game().unwrap().pickups().len() > pickups);
+    }
+
+    #[test]
+    fn the_keyboard_that_paused_can_resume() {
+        let mut ctx = context();
+        assert!(!ctx.settings.anyone_can_resume);
+        let mut stack = StateStack::new(Box::new(
+            Match::new(&ctx, vec![DeviceId::Keyboard]).unwrap(),
+        ));
+
+        // Pausing comes from raw key events, menu nav from window events
+        stack.input(&mut ctx, &nav(NavAction::Back));
+        assert_eq!(stack.entries.len(), 2);
+        let back = [(DeviceId::Keyboard, NavAction::Back)];
+        stack.ui(&mut ctx, &back, SCREEN);
+        stack.ui(&mut ctx, &back, SCREEN);
+        assert_eq!(stack.entries.len(), 1);
+    }
+
+    #[test]
+    fn focus_lost_pauses_the_match() {
+        let mut ctx = context();
+        let mut stack = StateStack::new(Box::new(
+            Match::new(&ctx, vec![DeviceId::Keyboard]).unwrap(),
+        ));
+        stack.focus_lost(&mut ctx);
+        assert_eq!(stack.entries.len(), 2);
+        assert!(stack.game().is_some());
+
+        // Anyone can resume when nobody paused
+        stack.ui(&mut ctx, &[], SCREEN);
+        let pause = &stack.entries[1].state;
+        assert!(pause.accepts_nav(&ctx, DeviceId::Keyboard));
+    }
+
+    #[test]
+    fn positions_round_trip_through_ui_space() {
+        let mut ctx = context();
+        ctx.settings.ui_scale = 1.5;
+        ctx.scale_factor = 2.0;
+        let screen = glam::vec2(1920.0, 720.0);
+        assert_eq!(ctx.screen_to_ui(screen), glam::vec2(640.0, 240.0));
+        assert_eq!(ctx.ui_to_screen(ctx.screen_to_ui(screen)), screen);
+
+        for split_screen in SplitScreen::ALL {
+            let mut game = Game::new(Duration::from_secs(1), ctx.animations.clone()).unwrap();
+            game.spawn_player();
+            game.spawn_player();
+            game.set_split_screen(split_screen);
+            game.set_scale_mode(ScaleMode::Letterbox);
+            game.resize(screen.x as u32, screen.y as u32);
+            game.tick(Duration::from_millis(16));
+
+            for view in game.views() {
+                for world in [glam::Vec2::ZERO, glam::vec2(30.0, -12.5)] {
+                    let ui = ctx.screen_to_ui(view.world_to_screen(world, screen));
+                    let back = view.screen_to_world(ctx.ui_to_screen(ui), screen);
+                    assert!(
+                        (back - world).length() < 1e-3,
+                        "{world} came back as {back}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    input::{DeviceId, DeviceInput, InputAction},
+    state::{Context, MainMenu, State, Transition, player_name, settings::SettingsMenu},
+    ui::{Panel, Ui},
+};
+
+/// Why a match was paused. Players are indices into the match's players.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Player { device: DeviceId, player: usize },
+    Disconnected { player: usize },
+    FocusLost,
+}
+
+/// Drawn over the match it paused, which stops updating while this is on top.
+#[derive(Debug)]
+pub struct Pause {
+    reason: PauseReason,
+}
+
+impl Pause {
+    pub fn new(reason: PauseReason) -> Self {
+        Self { reason }
+    }
+
+    /// The device that has to resume, if only one can.
+    fn owner(&self, ctx: &Context) -> Option<DeviceId> {
+        match self.reason {
+            PauseReason::Player { device, .. } if !ctx.settings.anyone_can_resume => Some(device),
+            _ => None,
+        }
+    }
+}
+
+impl State for Pause {
+    fn input(&mut self, _ctx: &mut Context, input: &DeviceInput) -> Transition {
+        // Nobody else could resume if the owner's controller goes away
+        if let PauseReason::Player { device, player } = self.reason
+            && input.device == device
+            && input.action == InputAction::Disconnected
+        {
+            self.reason = PauseReason::Disconnected { player };
+        }
+        Transition::None
+    }
+
+    fn accepts_nav(&self, ctx: &Context, device: DeviceId) -> bool {
+        self.owner(ctx).is_none_or(|owner| owner == device)
+    }
+
+    fn ui(&mut self, ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+        let reason = match self.reason {
+            PauseReason::Player { player, .. } => format!("Paused by {}", player_name(player)),
+            PauseReason::Disconnected { player } => {
+                format!("{}'s controller disconnected", player_name(player))
+            }
+            PauseReason::FocusLost => "Paused while away".to_string(),
+        };
+        let owner = match self.reason {
+            PauseReason::Player { player, .. } if self.owner(ctx).is_some() => Some(player),
+            _ => None,
+        };
+
+        Panel::new(560.0).show(ui, |ui| {
+            ui.title("Paused");
+            ui.label(&reason);
+            if let Some(player) = owner {
+                ui.label(&format!("Only {} can resume", player_name(player)));
+            }
+            ui.space(ui.theme().spacing);
+            if ui.button("Resume") || ui.back() {
+                return Transition::Pop;
+            }
+            // Saved right away like the settings menu would on leaving
+            if ui.toggle("Anyone can resume", &mut ctx.settings.anyone_can_resume)
+                && let Err(e) = ctx.settings.save()
+            {
+                log::error!("Unable to save settings: {e:#}");
+            }
+            if ui.button("Settings") {
+                return Transition::Push(Box::new(SettingsMenu::default()));
+            }
+            if ui.button("Quit to menu") {
+                return Transition::Reset(Box::new(MainMenu));
+            }
+            Transition::None
+        })
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
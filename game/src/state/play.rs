@@ -0,0 +1,203 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    game::{Game, Input, InputEvent, PlayerId},
+    input::{DeviceId, DeviceInput, InputAction},
+    render::{
+        PLAYER_COLORS,
+        rich_text::{SpanStyle, TextSpan},
+        text_layout::{HAlign, TextLayout, VAlign},
+        utils::Rect,
+    },
+    state::{
+        Context, State, Transition,
+        pause::{Pause, PauseReason},
+        results::Results,
+    },
+    ui::{NavAction, Ui},
+};
+
+const MATCH_LENGTH: Duration = Duration::from_secs(60);
+const PICKUP_TIMER: Duration = Duration::from_secs(1);
+
+/// Distance from the edge of the window to HUD elements, in UI units
+const UI_MARGIN: f32 = 20.0;
+
+pub struct Match {
+    game: Game,
+    /// Devices in the order they joined, which is also player order
+    devices: Vec<DeviceId>,
+    players: HashMap<DeviceId, PlayerId>,
+    remaining: Duration,
+}
+
+impl Match {
+    pub fn new(ctx: &Context, devices: Vec<DeviceId>) -> anyhow::Result<Self> {
+        let mut game = Game::new(PICKUP_TIMER, ctx.animations.clone())?;
+        game.set_split_screen(ctx.settings.split_screen);
+        game.set_scale_mode(ctx.settings.scale_mode);
+        let players = devices
+            .iter()
+            .map(|&device| (device, game.spawn_player()))
+            .collect();
+        Ok(Self {
+            game,
+            devices,
+            players,
+            remaining: MATCH_LENGTH,
+        })
+    }
+
+    /// Switches to a new match, or stays put if one can't be started.
+    pub fn start(ctx: &Context, devices: Vec<DeviceId>) -> Transition {
+        match Self::new(ctx, devices) {
+            Ok(game) => Transition::Replace(Box::new(game)),
+            Err(e) => {
+                log::error!("Unable to start a match: {e:#}");
+                Transition::None
+            }
+        }
+    }
+
+    fn player_index(&self, device: DeviceId) -> usize {
+        self.devices.iter().position(|&d| d == device).unwrap_or(0)
+    }
+
+    /// Stops everyone first, since releases while paused go to the pause
+    /// menu instead.
+    fn pause(&mut self, reason: PauseReason) -> Transition {
+        for &id in self.players.values() {
+            self.game.handle_input(InputEvent {
+                id,
+                input: Input::X(0.0),
+            });
+            self.game.handle_input(InputEvent {
+                id,
+                input: Input::Y(0.0),
+            });
+        }
+        Transition::Push(Box::new(Pause::new(reason)))
+    }
+}
+
+impl State for Match {
+    fn input(&mut self, _ctx: &mut Context, input: &DeviceInput) -> Transition {
+        let Some(&id) = self.players.get(&input.device) else {
+            return Transition::None;
+        };
+        let input = match input.action {
+            InputAction::MoveX(amount) => Input::X(amount),
+            InputAction::MoveY(amount) => Input::Y(amount),
+            InputAction::Nav(NavAction::Back) => {
+                return self.pause(PauseReason::Player {
+                    device: input.device,
+                    player: self.player_index(input.device),
+                });
+            }
+            InputAction::Nav(_) | InputAction::Key(_) => return Transition::None,
+            InputAction::Disconnected => {
+                return self.pause(PauseReason::Disconnected {
+                    player: self.player_index(input.device),
+                });
+            }
+        };
+        self.game.handle_input(InputEvent { id, input });
+        Transition::None
+    }
+
+    fn focus_lost(&mut self, _ctx: &mut Context) -> Transition {
+        self.pause(PauseReason::FocusLost)
+    }
+
+    fn update(&mut self, ctx: &mut Context, dt: Duration) -> Transition {
+        self.game.set_split_screen(ctx.settings.split_screen);
+        self.game.set_scale_mode(ctx.settings.scale_mode);
+        self.game.set_debug_draw(ctx.debug_draw);
+        self.game.set_particle_budget(ctx.settings.particle_budget);
+        self.game.tick(dt);
+        self.remaining = self.remaining.saturating_sub(dt);
+        if self.remaining.is_zero() {
+            let scores = self.game.players().iter().map(|p| p.score).collect();
+            return Transition::Replace(Box::new(Results::new(scores, self.devices.clone())));
+        }
+        Transition::None
+    }
+
+    fn ui(&mut self, ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+        // Lines along the top and left of views that don't touch those edges
+        let screen = ui.region();
+        let width = 2.0 * ui.theme().scale;
+        let color = ui.theme().panel_color;
+        for view in self.game.views() {
+            let area = Rect::new(
+                screen.min + view.area.min * screen.size(),
+                screen.min + view.area.max * screen.size(),
+            );
+            if view.area.min.x > 0.0 {
+                let min = area.min - glam::vec2(width * 0.5, 0.0);
+                ui.rect(Rect::new(min, glam::vec2(min.x + width, area.max.y)), color);
+            }
+            if view.area.min.y > 0.0 {
+                let min = area.min - glam::vec2(0.0, width * 0.5);
+                ui.rect(Rect::new(min, glam::vec2(area.max.x, min.y + width)), color);
+            }
+        }
+
+        let margin = ctx.ui_to_screen(glam::Vec2::splat(UI_MARGIN));
+        let bounds = Rect::new(screen.min + margin, screen.max - margin);
+        let text_size = ui.theme().text_size;
+
+        let scores = self
+            .game
+            .players()
+            .iter()
+            .enumerate()
+            .map(|(i, player)| {
+                TextSpan::new(
+                    format!("Player {}: {}\n", i + 1, player.score),
+                    SpanStyle::default()
+                        .with_color(PLAYER_COLORS[i % PLAYER_COLORS.len()])
+                        .with_size(text_size),
+                )
+            })
+            .collect();
+        ui.text(
+            scores,
+            TextLayout::in_rect(bounds).align(HAlign::Right, VAlign::Top),
+        );
+
+        let seconds = self.remaining.as_secs_f32().ceil() as u32;
+        ui.text(
+            vec![TextSpan::new(
+                format!("{}:{:02}", seconds / 60, seconds % 60),
+                SpanStyle::default().with_size(ui.theme().title_size),
+            )],
+            TextLayout::in_rect(bounds).align(HAlign::Center, VAlign::Top),
+        );
+
+        if ctx.debug_draw {
+            // To check DPI and UI scaling against
+            let size = screen.size();
+            let ui_size = ctx.screen_to_ui(size);
+            ui.text(
+                vec![TextSpan::new(
+                    format!(
+                        "{}x{} px, {}x{} UI",
+                        size.x as u32, size.y as u32, ui_size.x as u32, ui_size.y as u32
+                    ),
+                    SpanStyle::default().with_size(text_size),
+                )],
+                TextLayout::in_rect(bounds),
+            );
+        }
+        Transition::None
+    }
+
+    fn game(&self) -> Option<&Game> {
+        Some(&self.game)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.game.resize(width, height);
+    }
+}
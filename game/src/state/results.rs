@@ -0,0 +1,46 @@
+use crate::{
+    input::DeviceId,
+    state::{Context, MainMenu, State, Transition, play::Match, player_name},
+    ui::{Panel, Ui},
+};
+
+pub struct Results {
+    scores: Vec<i32>,
+    /// So the same players can go again
+    devices: Vec<DeviceId>,
+}
+
+impl Results {
+    pub fn new(scores: Vec<i32>, devices: Vec<DeviceId>) -> Self {
+        Self { scores, devices }
+    }
+}
+
+impl State for Results {
+    fn ui(&mut self, ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+        let best = self.scores.iter().copied().max();
+        let mut ranking = (0..self.scores.len()).collect::<Vec<_>>();
+        ranking.sort_by_key(|&i| std::cmp::Reverse(self.scores[i]));
+
+        Panel::new(560.0).show(ui, |ui| {
+            ui.title("Results");
+            for i in ranking {
+                let score = self.scores[i];
+                let winner = if Some(score) == best {
+                    " [color=yellow]wins![/color]"
+                } else {
+                    ""
+                };
+                ui.label(&format!("{}: {score}{winner}", player_name(i)));
+            }
+            ui.space(ui.theme().spacing);
+            if ui.button("Play again") {
+                return Match::start(ctx, self.devices.clone());
+            }
+            if ui.button("Main menu") || ui.back() {
+                return Transition::Reset(Box::new(MainMenu));
+            }
+            Transition::None
+        })
+    }
+}
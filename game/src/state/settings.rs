@@ -0,0 +1,314 @@
+use winit::keyboard::KeyCode;
+
+use crate::{
+    game::camera::{ScaleMode, SplitScreen},
+    input::{DeviceId, DeviceInput, InputAction},
+    render::PresentMode,
+    settings::{Bindings, Settings, WindowMode},
+    state::{Context, State, Transition},
+    ui::{NavAction, Panel, Ui},
+};
+
+const RESOLUTIONS: [[u32; 2]; 5] = [
+    [1280, 720],
+    [1600, 900],
+    [1920, 1080],
+    [2560, 1440],
+    [3840, 2160],
+];
+
+const PARTICLE_BUDGETS: [(&str, usize); 4] =
+    [("Off", 0), ("Low", 500), ("Medium", 2000), ("High", 8000)];
+
+const MSAA_SAMPLES: [(&str, u32); 4] = [("Off", 1), ("2x", 2), ("4x", 4), ("8x", 8)];
+
+const FRAME_RATE_CAPS: [Option<u32>; 6] =
+    [None, Some(30), Some(60), Some(120), Some(144), Some(240)];
+
+/// Settings are split up so each page fits a 720p window.
+const PAGES: [&str; 4] = ["Display", "Graphics", "Gameplay", "Controls"];
+const DISPLAY: usize = 0;
+const GRAPHICS: usize = 1;
+const GAMEPLAY: usize = 2;
+const CONTROLS: usize = 3;
+
+/// Changes apply as soon as they're made, and are saved when leaving.
+#[derive(Debug, Default)]
+pub struct SettingsMenu {
+    /// Index into [`PAGES`]
+    page: usize,
+    /// Direction waiting for a key to be bound to it
+    capturing: Option<usize>,
+    /// A key was just bound, so the press shouldn't also navigate
+    captured: bool,
+}
+
+impl State for SettingsMenu {
+    fn input(&mut self, ctx: &mut Context, input: &DeviceInput) -> Transition {
+        let Some(direction) = self.capturing else {
+            return Transition::None;
+        };
+        match input.action {
+            InputAction::Key(KeyCode::Escape) | InputAction::Nav(NavAction::Back) => {}
+            InputAction::Key(key) => {
+                for keys in ctx.settings.bindings.directions_mut() {
+                    keys.retain(|&k| k != key);
+                }
+                *ctx.settings.bindings.directions_mut()[direction] = vec![key];
+            }
+            _ => return Transition::None,
+        }
+        self.capturing = None;
+        self.captured = true;
+        Transition::None
+    }
+
+    fn accepts_nav(&self, _ctx: &Context, _device: DeviceId) -> bool {
+        self.capturing.is_none() && !self.captured
+    }
+
+    fn ui(&mut self, ctx: &mut Context, ui: &mut Ui<'_>) -> Transition {
+        self.captured = false;
+        let settings = &mut ctx.settings;
+
+        let capturing = self.capturing;
+        let mut capture = None;
+        let mut back = false;
+        let mut page = self.page;
+        Panel::new(640.0).show(ui, |ui| {
+            ui.title("Settings");
+            ui.selector("Page", &PAGES, &mut page);
+            ui.space(ui.theme().spacing);
+
+            match page {
+                DISPLAY => display_page(ui, settings),
+                GRAPHICS => graphics_page(ui, settings),
+                GAMEPLAY => gameplay_page(ui, settings),
+                CONTROLS => {
+                    capture = controls_page(ui, settings, capturing);
+                }
+                _ => {}
+            }
+
+            ui.space(ui.theme().spacing);
+            if ui.button("Reset to defaults") {
+                *settings = Settings::default();
+            }
+            back = ui.button("Back") || ui.back();
+        });
+        self.page = page;
+
+        if capture.is_some() {
+            self.capturing = capture;
+        }
+        if back {
+            if let Err(e) = ctx.settings.save() {
+                log::error!("Unable to save settings: {e:#}");
+            }
+            return Transition::Pop;
+        }
+        Transition::None
+    }
+}
+
+fn display_page(ui: &mut Ui<'_>, settings: &mut Settings) {
+    let mut mode = WindowMode::ALL
+        .iter()
+        .position(|&mode| mode == settings.window_mode)
+        .unwrap_or(0);
+    let modes = WindowMode::ALL.map(WindowMode::name);
+    if ui.selector("Window", &modes, &mut mode) {
+        settings.window_mode = WindowMode::ALL[mode];
+    }
+
+    let mut resolutions = RESOLUTIONS.to_vec();
+    if !resolutions.contains(&settings.resolution) {
+        resolutions.insert(0, settings.resolution);
+    }
+    let resolution_names = resolutions
+        .iter()
+        .map(|[width, height]| format!("{width}x{height}"))
+        .collect::<Vec<_>>();
+    let mut resolution = resolutions
+        .iter()
+        .position(|&r| r == settings.resolution)
+        .unwrap_or(0);
+    if ui.selector("Resolution", &resolution_names, &mut resolution) {
+        settings.resolution = resolutions[resolution];
+    }
+
+    let mut present_mode = PresentMode::ALL
+        .iter()
+        .position(|&mode| mode == settings.present_mode)
+        .unwrap_or(0);
+    let present_modes = PresentMode::ALL.map(PresentMode::name);
+    if ui.selector("Present mode", &present_modes, &mut present_mode) {
+        settings.present_mode = PresentMode::ALL[present_mode];
+    }
+
+    let mut latency = settings.max_frame_latency.clamp(1, 3) as usize - 1;
+    if ui.selector("Frame latency", &["1", "2", "3"], &mut latency) {
+        settings.max_frame_latency = latency as u32 + 1;
+    }
+
+    let mut caps = FRAME_RATE_CAPS.to_vec();
+    if !caps.contains(&settings.frame_rate_cap) {
+        caps.push(settings.frame_rate_cap);
+    }
+    let cap_names = caps
+        .iter()
+        .map(|cap| match cap {
+            Some(fps) => format!("{fps} fps"),
+            None => "Off".to_string(),
+        })
+        .collect::<Vec<_>>();
+    let mut cap = caps
+        .iter()
+        .position(|&cap| cap == settings.frame_rate_cap)
+        .unwrap_or(0);
+    if ui.selector("Frame rate cap", &cap_names, &mut cap) {
+        settings.frame_rate_cap = caps[cap];
+    }
+
+    let scale = format!("UI scale {:.0}%", settings.ui_scale * 100.0);
+    ui.slider(&scale, &mut settings.ui_scale, 0.5..=2.0, 0.25);
+}
+
+fn graphics_page(ui: &mut Ui<'_>, settings: &mut Settings) {
+    let mut msaa = MSAA_SAMPLES
+        .iter()
+        .position(|&(_, samples)| samples == settings.msaa_samples)
+        .unwrap_or(0);
+    let msaa_names = MSAA_SAMPLES.map(|(name, _)| name);
+    if ui.selector("Antialiasing", &msaa_names, &mut msaa) {
+        settings.msaa_samples = MSAA_SAMPLES[msaa].1;
+    }
+
+    // Budgets edited into the file show as the closest option
+    let mut budget = (0..PARTICLE_BUDGETS.len())
+        .min_by_key(|&i| PARTICLE_BUDGETS[i].1.abs_diff(settings.particle_budget))
+        .unwrap_or(0);
+    let budgets = PARTICLE_BUDGETS.map(|(name, _)| name);
+    if ui.selector("Particles", &budgets, &mut budget) {
+        settings.particle_budget = PARTICLE_BUDGETS[budget].1;
+    }
+
+    let post = &mut settings.post_process;
+    ui.toggle("Bloom", &mut post.bloom);
+    let vignette = format!("Vignette {:.0}%", post.vignette * 100.0);
+    ui.slider(&vignette, &mut post.vignette, 0.0..=1.0, 0.1);
+    let aberration = format!(
+        "Chromatic aberration {:.0}%",
+        post.chromatic_aberration * 100.0
+    );
+    ui.slider(&aberration, &mut post.chromatic_aberration, 0.0..=1.0, 0.25);
+    ui.toggle("CRT", &mut post.crt);
+}
+
+fn gameplay_page(ui: &mut Ui<'_>, settings: &mut Settings) {
+    let volume = format!("Volume {:.0}%", settings.volume * 100.0);
+    ui.slider(&volume, &mut settings.volume, 0.0..=1.0, 0.1);
+    ui.toggle("Anyone can resume", &mut settings.anyone_can_resume);
+
+    let mut split = SplitScreen::ALL
+        .iter()
+        .position(|&split| split == settings.split_screen)
+        .unwrap_or(0);
+    let splits = SplitScreen::ALL.map(SplitScreen::name);
+    if ui.selector("Split screen", &splits, &mut split) {
+        settings.split_screen = SplitScreen::ALL[split];
+    }
+
+    let mut scale_mode = ScaleMode::ALL
+        .iter()
+        .position(|&mode| mode == settings.scale_mode)
+        .unwrap_or(0);
+    let scale_modes = ScaleMode::ALL.map(ScaleMode::name);
+    if ui.selector("Scaling", &scale_modes, &mut scale_mode) {
+        settings.scale_mode = ScaleMode::ALL[scale_mode];
+    }
+}
+
+/// Returns the direction to bind a key to when one of them was picked.
+fn controls_page(
+    ui: &mut Ui<'_>,
+    settings: &mut Settings,
+    capturing: Option<usize>,
+) -> Option<usize> {
+    let mut capture = None;
+    for (i, keys) in settings.bindings.directions().into_iter().enumerate() {
+        let keys = if capturing == Some(i) {
+            "[color=yellow]press a key[/color]".to_string()
+        } else if keys.is_empty() {
+            "[color=gray]unbound[/color]".to_string()
+        } else {
+            keys.iter()
+                .copied()
+                .map(key_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if ui.button(&format!("{}: {keys}", Bindings::NAMES[i])) {
+            capture = Some(i);
+        }
+    }
+    capture
+}
+
+/// Short names for keys, eg. `W` rather than `KeyW`.
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    ["Key", "Digit", "Arrow"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{
+        StateStack,
+        tests::{SCREEN, context},
+    };
+
+    #[test]
+    fn every_page_fits_720p() {
+        let mut ctx = context();
+        for (page, name) in PAGES.iter().enumerate() {
+            let menu = SettingsMenu {
+                page,
+                ..Default::default()
+            };
+            let mut stack = StateStack::new(Box::new(menu));
+            let draw = stack.ui(&mut ctx, &[], SCREEN);
+            for quad in &draw.quads {
+                assert!(
+                    quad.rect.min.y >= 0.0 && quad.rect.max.y <= SCREEN.max.y,
+                    "{name} page doesn't fit: {:?}",
+                    quad.rect
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn left_and_right_switch_pages() {
+        let mut ctx = context();
+        let mut stack = StateStack::new(Box::new(SettingsMenu::default()));
+        stack.ui(&mut ctx, &[], SCREEN);
+        let right = [(DeviceId::Keyboard, NavAction::Right)];
+        let page = |stack: &mut StateStack, ctx: &mut Context, actions| {
+            stack
+                .ui(ctx, actions, SCREEN)
+                .texts
+                .iter()
+                .flat_map(|text| &text.spans)
+                .any(|span| span.text.contains("Antialiasing"))
+        };
+        assert!(!page(&mut stack, &mut ctx, &[]));
+        assert!(page(&mut stack, &mut ctx, &right));
+        assert!(page(&mut stack, &mut ctx, &[]));
+    }
+}
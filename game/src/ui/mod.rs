@@ -0,0 +1,415 @@
+//! Immediate mode UI for menus and the HUD.
+//!
+//! The UI is rebuilt every frame by calling widget methods on a [`Ui`], which
+//! returns what happened to them this frame. Widgets are navigated with
+//! [`NavAction`]s from the keyboard or a gamepad, one widget at a time has
+//! focus. The result is a [`DrawList`] in UI space (pixels, origin in the top
+//! left) that the renderer draws with the UI camera.
+
+mod widgets;
+
+pub use widgets::Panel;
+
+use crate::render::{
+    rich_text::TextSpan,
+    text_layout::{HAlign, TextLayout, VAlign},
+    utils::Rect,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Accept,
+    Back,
+}
+
+/// What the UI remembers between frames.
+#[derive(Debug, Default)]
+pub struct UiState {
+    focus: usize,
+    /// Focusable widgets last frame, so focus can wrap around
+    focusable: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Applied to sizes passed to widgets, eg. panel widths. The other sizes
+    /// here are already scaled.
+    pub scale: f32,
+    pub text_size: f32,
+    pub title_size: f32,
+    pub row_height: f32,
+    pub spacing: f32,
+    pub padding: f32,
+    pub text_color: glam::Vec4,
+    pub panel_color: glam::Vec4,
+    pub widget_color: glam::Vec4,
+    pub focus_color: glam::Vec4,
+    pub accent_color: glam::Vec4,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            text_size: 28.0,
+            title_size: 42.0,
+            row_height: 44.0,
+            spacing: 8.0,
+            padding: 16.0,
+            text_color: glam::vec4(1.0, 1.0, 1.0, 1.0),
+            panel_color: glam::vec4(0.05, 0.05, 0.08, 0.85),
+            widget_color: glam::vec4(0.2, 0.2, 0.25, 1.0),
+            focus_color: glam::vec4(0.35, 0.35, 0.6, 1.0),
+            accent_color: glam::vec4(1.0, 0.8, 0.2, 1.0),
+        }
+    }
+}
+
+impl Theme {
+    /// Multiplies every size by `scale`.
+    pub fn scaled(self, scale: f32) -> Self {
+        Self {
+            scale: self.scale * scale,
+            text_size: self.text_size * scale,
+            title_size: self.title_size * scale,
+            row_height: self.row_height * scale,
+            spacing: self.spacing * scale,
+            padding: self.padding * scale,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UiQuad {
+    pub rect: Rect,
+    pub color: glam::Vec4,
+}
+
+#[derive(Debug, Clone)]
+pub struct UiText {
+    pub spans: Vec<TextSpan>,
+    pub layout: TextLayout,
+    /// Drawn with an outline and shadow so it reads over the game
+    pub outlined: bool,
+}
+
+/// Everything the UI drew this frame. Text is drawn over all of the quads.
+#[derive(Debug, Clone, Default)]
+pub struct DrawList {
+    pub quads: Vec<UiQuad>,
+    pub texts: Vec<UiText>,
+}
+
+impl DrawList {
+    /// Adds `other` on top of this list.
+    pub fn append(&mut self, mut other: DrawList) {
+        self.quads.append(&mut other.quads);
+        self.texts.append(&mut other.texts);
+    }
+}
+
+pub struct Ui<'a> {
+    state: &'a mut UiState,
+    theme: Theme,
+    /// Actions for the focused widget
+    pending: Vec<NavAction>,
+    back: bool,
+    draw: DrawList,
+    /// Widgets are placed top to bottom in this rect
+    region: Rect,
+    cursor: f32,
+    next_focus: usize,
+}
+
+impl<'a> Ui<'a> {
+    /// Starts a frame of UI covering `screen`. Up and down move focus right
+    /// away, the other actions go to the focused widget.
+    pub fn new(state: &'a mut UiState, actions: &[NavAction], screen: Rect) -> Self {
+        let mut pending = Vec::new();
+        let mut back = false;
+        for &action in actions {
+            let count = state.focusable;
+            match action {
+                NavAction::Up if count > 0 => state.focus = (state.focus + count - 1) % count,
+                NavAction::Down if count > 0 => state.focus = (state.focus + 1) % count,
+                NavAction::Back => back = true,
+                NavAction::Up | NavAction::Down => {}
+                action => pending.push(action),
+            }
+        }
+
+        Self {
+            state,
+            theme: Theme::default(),
+            pending,
+            back,
+            draw: DrawList::default(),
+            region: screen,
+            cursor: screen.min.y,
+            next_focus: 0,
+        }
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// The rect widgets are currently placed in.
+    pub fn region(&self) -> Rect {
+        self.region
+    }
+
+    /// Whether back was pressed this frame.
+    pub fn back(&self) -> bool {
+        self.back
+    }
+
+    /// Draws a rect without taking up space.
+    pub fn rect(&mut self, rect: Rect, color: glam::Vec4) {
+        self.draw.quads.push(UiQuad { rect, color });
+    }
+
+    /// Draws text without taking up space, for HUD elements placed by hand.
+    pub fn text(&mut self, spans: Vec<TextSpan>, layout: TextLayout) {
+        self.draw.texts.push(UiText {
+            spans,
+            layout,
+            outlined: true,
+        });
+    }
+
+    /// Leaves a gap before the next widget.
+    pub fn space(&mut self, height: f32) {
+        self.cursor += height;
+    }
+
+    pub fn finish(self) -> DrawList {
+        self.state.focusable = self.next_focus;
+        if self.next_focus > 0 && self.state.focus >= self.next_focus {
+            self.state.focus = self.next_focus - 1;
+        }
+        self.draw
+    }
+
+    /// Takes the next row of the region.
+    fn allocate(&mut self, height: f32) -> Rect {
+        let rect = Rect::new(
+            glam::vec2(self.region.min.x, self.cursor),
+            glam::vec2(self.region.max.x, self.cursor + height),
+        );
+        self.cursor += height + self.theme.spacing;
+        rect
+    }
+
+    /// Registers a focusable widget, returning whether it has focus.
+    fn focus(&mut self) -> bool {
+        let focused = self.state.focus == self.next_focus;
+        self.next_focus += 1;
+        focused
+    }
+
+    /// Whether the focused widget got `action` this frame. Only call this for
+    /// the focused widget.
+    fn pressed(&self, action: NavAction) -> bool {
+        self.pending.contains(&action)
+    }
+
+    /// Net left/right presses, for widgets that step through values.
+    fn steps(&self) -> i32 {
+        self.pending
+            .iter()
+            .map(|action| match action {
+                NavAction::Left => -1,
+                NavAction::Right => 1,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn widget_color(&self, focused: bool) -> glam::Vec4 {
+        if focused {
+            self.theme.focus_color
+        } else {
+            self.theme.widget_color
+        }
+    }
+
+    fn push_label(&mut self, spans: Vec<TextSpan>, rect: Rect, h_align: HAlign) {
+        let padding = self.theme.padding * 0.5;
+        let bounds = Rect::new(
+            rect.min + glam::vec2(padding, 0.0),
+            rect.max - glam::vec2(padding, 0.0),
+        );
+        self.draw.texts.push(UiText {
+            spans,
+            layout: TextLayout::in_rect(bounds).align(h_align, VAlign::Middle),
+            outlined: false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Rect {
+        Rect::new(glam::Vec2::ZERO, glam::vec2(640.0, 360.0))
+    }
+
+    /// A frame of three buttons, returning which were activated.
+    fn buttons(state: &mut UiState, actions: &[NavAction]) -> [bool; 3] {
+        let mut ui = Ui::new(state, actions, screen());
+        let pressed = ["One", "Two", "Three"].map(|text| ui.button(text));
+        ui.finish();
+        pressed
+    }
+
+    #[test]
+    fn up_and_down_move_focus() {
+        let mut state = UiState::default();
+        buttons(&mut state, &[]);
+        assert_eq!(state.focus, 0);
+
+        buttons(&mut state, &[NavAction::Down]);
+        assert_eq!(state.focus, 1);
+        buttons(&mut state, &[NavAction::Down, NavAction::Down]);
+        assert_eq!(state.focus, 0);
+        buttons(&mut state, &[NavAction::Down, NavAction::Up]);
+        assert_eq!(state.focus, 0);
+    }
+
+    #[test]
+    fn focus_wraps_around() {
+        let mut state = UiState::default();
+        buttons(&mut state, &[]);
+
+        buttons(&mut state, &[NavAction::Up]);
+        assert_eq!(state.focus, 2);
+        buttons(&mut state, &[NavAction::Down]);
+        assert_eq!(state.focus, 0);
+    }
+
+    #[test]
+    fn focus_is_kept_in_range_when_widgets_go_away() {
+        let mut state = UiState::default();
+        buttons(&mut state, &[]);
+        buttons(&mut state, &[NavAction::Up]);
+
+        let mut ui = Ui::new(&mut state, &[], screen());
+        ui.button("Only");
+        ui.finish();
+        assert_eq!(state.focus, 0);
+    }
+
+    #[test]
+    fn accept_activates_the_focused_widget() {
+        let mut state = UiState::default();
+        assert_eq!(buttons(&mut state, &[]), [false; 3]);
+        assert_eq!(
+            buttons(&mut state, &[NavAction::Accept]),
+            [true, false, false]
+        );
+        // Moves happen before the accept
+        assert_eq!(
+            buttons(&mut state, &[NavAction::Down, NavAction::Accept]),
+            [false, true, false]
+        );
+        // Left and right don't activate buttons
+        assert_eq!(
+            buttons(&mut state, &[NavAction::Left, NavAction::Right]),
+            [false; 3]
+        );
+    }
+
+    #[test]
+    fn back_goes_to_the_ui_not_the_widgets() {
+        let mut state = UiState::default();
+        let mut ui = Ui::new(&mut state, &[NavAction::Back], screen());
+        assert!(ui.back());
+        assert!(!ui.button("One"));
+    }
+
+    #[test]
+    fn toggle_flips_on_accept_or_step() {
+        let mut state = UiState::default();
+        let mut value = false;
+        for (action, expected) in [
+            (NavAction::Accept, true),
+            (NavAction::Left, false),
+            (NavAction::Right, true),
+        ] {
+            let mut ui = Ui::new(&mut state, &[action], screen());
+            assert!(ui.toggle("Toggle", &mut value));
+            ui.finish();
+            assert_eq!(value, expected);
+        }
+    }
+
+    #[test]
+    fn slider_steps_and_clamps() {
+        let mut state = UiState::default();
+        let mut value = 0.5;
+        let mut frame = |actions: &[NavAction], value: &mut f32| {
+            let mut ui = Ui::new(&mut state, actions, screen());
+            let changed = ui.slider("Slider", value, 0.0..=1.0, 0.25);
+            ui.finish();
+            changed
+        };
+
+        assert!(frame(&[NavAction::Right], &mut value));
+        assert_eq!(value, 0.75);
+        assert!(frame(&[NavAction::Left, NavAction::Left], &mut value));
+        assert_eq!(value, 0.25);
+        assert!(frame(&[NavAction::Right; 3], &mut value));
+        assert_eq!(value, 1.0);
+        assert!(!frame(&[NavAction::Right], &mut value));
+        assert_eq!(value, 1.0);
+        assert!(frame(&[NavAction::Left; 5], &mut value));
+        assert_eq!(value, 0.0);
+        assert!(!frame(&[NavAction::Accept], &mut value));
+    }
+
+    #[test]
+    fn unfocused_slider_doesnt_move() {
+        let mut state = UiState::default();
+        let mut value = 0.5;
+        let mut ui = Ui::new(&mut state, &[NavAction::Right], screen());
+        ui.button("Focused");
+        assert!(!ui.slider("Slider", &mut value, 0.0..=1.0, 0.25));
+        ui.finish();
+        assert_eq!(value, 0.5);
+    }
+
+    #[test]
+    fn selector_cycles_both_ways() {
+        let mut state = UiState::default();
+        let options = ["a", "b", "c"];
+        let mut selected = 0;
+        let mut frame = |actions: &[NavAction], selected: &mut usize| {
+            let mut ui = Ui::new(&mut state, actions, screen());
+            let changed = ui.selector("Selector", &options, selected);
+            ui.finish();
+            changed
+        };
+
+        assert!(frame(&[NavAction::Left], &mut selected));
+        assert_eq!(selected, 2);
+        assert!(frame(&[NavAction::Right], &mut selected));
+        assert_eq!(selected, 0);
+        assert!(frame(&[NavAction::Right; 4], &mut selected));
+        assert_eq!(selected, 1);
+        // A full cycle ends up where it started
+        assert!(!frame(&[NavAction::Left; 3], &mut selected));
+        assert_eq!(selected, 1);
+    }
+}
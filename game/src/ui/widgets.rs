@@ -0,0 +1,191 @@
+use std::ops::RangeInclusive;
+
+use crate::{
+    render::{
+        rich_text::{SpanStyle, TextSpan, parse_markup},
+        text_layout::HAlign,
+        utils::Rect,
+    },
+    ui::{NavAction, Ui, UiQuad},
+};
+
+/// A background that sizes itself to the widgets added to it. Panels are
+/// centered in the enclosing region rather than placed after the previous
+/// widget.
+pub struct Panel {
+    width: f32,
+}
+
+impl Panel {
+    pub fn new(width: f32) -> Self {
+        Self { width }
+    }
+
+    pub fn show<R>(self, ui: &mut Ui<'_>, f: impl FnOnce(&mut Ui<'_>) -> R) -> R {
+        let padding = ui.theme.padding;
+        let width = self.width * ui.theme.scale;
+        let background = ui.draw.quads.len();
+        ui.draw.quads.push(UiQuad {
+            rect: Rect::ZERO,
+            color: ui.theme.panel_color,
+        });
+        let first_quad = ui.draw.quads.len();
+        let first_text = ui.draw.texts.len();
+
+        // Lay out from the origin, then move everything once the size is known
+        let outer = ui.region;
+        let outer_cursor = ui.cursor;
+        ui.region = Rect::new(
+            glam::vec2(padding, padding),
+            glam::vec2(width - padding, f32::INFINITY),
+        );
+        ui.cursor = padding;
+
+        let result = f(ui);
+
+        let height = (ui.cursor - ui.theme.spacing).max(padding) + padding;
+        ui.region = outer;
+        ui.cursor = outer_cursor;
+
+        let offset = outer.center() - glam::vec2(width, height) * 0.5;
+
+        ui.draw.quads[background].rect = Rect::from_pos_size(offset, glam::vec2(width, height));
+        for quad in &mut ui.draw.quads[first_quad..] {
+            quad.rect = quad.rect.translate(offset);
+        }
+        for text in &mut ui.draw.texts[first_text..] {
+            text.layout.bounds = text.layout.bounds.translate(offset);
+        }
+
+        result
+    }
+}
+
+impl Ui<'_> {
+    fn text_style(&self) -> SpanStyle {
+        SpanStyle::default()
+            .with_color(self.theme.text_color)
+            .with_size(self.theme.text_size)
+    }
+
+    /// Large centered text.
+    pub fn title(&mut self, text: &str) {
+        let style = self.text_style().with_size(self.theme.title_size);
+        let rect = self.allocate(self.theme.title_size * 1.4);
+        self.push_label(parse_markup(text, style), rect, HAlign::Center);
+    }
+
+    /// Text with markup, see [`crate::render::rich_text`].
+    pub fn label(&mut self, text: &str) {
+        let spans = parse_markup(text, self.text_style());
+        let rect = self.allocate(self.theme.row_height);
+        self.push_label(spans, rect, HAlign::Left);
+    }
+
+    /// Returns true when activated.
+    pub fn button(&mut self, text: &str) -> bool {
+        let focused = self.focus();
+        let rect = self.allocate(self.theme.row_height);
+        let color = self.widget_color(focused);
+        self.rect(rect, color);
+        self.push_label(parse_markup(text, self.text_style()), rect, HAlign::Center);
+        focused && self.pressed(NavAction::Accept)
+    }
+
+    /// Flipped by accept, left or right. Returns true when changed.
+    pub fn toggle(&mut self, text: &str, value: &mut bool) -> bool {
+        let focused = self.focus();
+        let changed = focused && (self.pressed(NavAction::Accept) || self.steps() != 0);
+        if changed {
+            *value = !*value;
+        }
+
+        let rect = self.allocate(self.theme.row_height);
+        let color = self.widget_color(focused);
+        self.rect(rect, color);
+        self.push_label(parse_markup(text, self.text_style()), rect, HAlign::Left);
+
+        let (state, color) = if *value {
+            ("On", self.theme.accent_color)
+        } else {
+            (
+                "Off",
+                self.theme.text_color * glam::vec4(0.6, 0.6, 0.6, 1.0),
+            )
+        };
+        let style = self.text_style().with_color(color);
+        self.push_label(vec![TextSpan::new(state, style)], rect, HAlign::Right);
+        changed
+    }
+
+    /// Moves by `step` with left and right. Returns true when changed.
+    pub fn slider(
+        &mut self,
+        text: &str,
+        value: &mut f32,
+        range: RangeInclusive<f32>,
+        step: f32,
+    ) -> bool {
+        let focused = self.focus();
+        let (min, max) = (*range.start(), *range.end());
+        let old = *value;
+        if focused {
+            *value += self.steps() as f32 * step;
+        }
+        *value = value.clamp(min, max);
+        let changed = *value != old;
+
+        let rect = self.allocate(self.theme.row_height);
+        let color = self.widget_color(focused);
+        self.rect(rect, color);
+        self.push_label(parse_markup(text, self.text_style()), rect, HAlign::Left);
+
+        // The track takes up the right half of the row
+        let padding = self.theme.padding * 0.5;
+        let track = Rect::new(
+            glam::vec2(rect.center().x, rect.center().y - 4.0),
+            glam::vec2(rect.max.x - padding, rect.center().y + 4.0),
+        );
+        let t = if max > min {
+            (*value - min) / (max - min)
+        } else {
+            0.0
+        };
+        let fill = Rect::new(
+            track.min,
+            glam::vec2(track.min.x + track.width() * t, track.max.y),
+        );
+        self.rect(track, self.theme.panel_color);
+        self.rect(fill, self.theme.accent_color);
+        changed
+    }
+
+    /// Cycles through `options` with left and right. Returns true when
+    /// changed.
+    pub fn selector(
+        &mut self,
+        text: &str,
+        options: &[impl AsRef<str>],
+        selected: &mut usize,
+    ) -> bool {
+        let focused = self.focus();
+        let old = *selected;
+        if focused && !options.is_empty() {
+            let count = options.len() as i32;
+            *selected = (*selected as i32 + self.steps()).rem_euclid(count) as usize;
+        }
+        let changed = *selected != old;
+
+        let rect = self.allocate(self.theme.row_height);
+        let color = self.widget_color(focused);
+        self.rect(rect, color);
+        self.push_label(parse_markup(text, self.text_style()), rect, HAlign::Left);
+
+        if let Some(option) = options.get(*selected) {
+            let value = format!("< {} >", option.as_ref());
+            let style = self.text_style().with_color(self.theme.accent_color);
+            self.push_label(vec![TextSpan::new(value, style)], rect, HAlign::Right);
+        }
+        changed
+    }
+}